@@ -0,0 +1,55 @@
+//! Compares the ways of turning a state map with large arrays into its
+//! struct: deserializing from borrowed values (`to_typed`), moving them out
+//! (`into_typed`), and the clone-then-`from_value` approach both replace.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use map_to_struct::{MapToStruct, StateMap};
+use serde_json::{json, Value};
+
+#[derive(Debug, MapToStruct)]
+struct Session {
+    title: String,
+    volume: u8,
+    recent_files: Vec<String>,
+    waveform: Vec<f32>,
+}
+
+fn session_state() -> StateMap<Session> {
+    let mut map = StateMap::new();
+    map.set("title", json!("Evening practice"));
+    map.set("volume", json!(7));
+    let files: Vec<_> = (0..2_000).map(|i| format!("/home/cat/recordings/take-{:04}.wav", i)).collect();
+    map.set("recent_files", json!(files));
+    let waveform: Vec<_> = (0..20_000).map(|i| (i as f32 * 0.01).sin()).collect();
+    map.set("waveform", json!(waveform));
+    map
+}
+
+// What `extract_field` used to do for every field
+fn cloned<T: serde::de::DeserializeOwned>(map: &StateMap<Session>, key: &str) -> T {
+    let value: Value = map.get(key).cloned().unwrap();
+    serde_json::from_value(value).unwrap()
+}
+
+fn conversion(c: &mut Criterion) {
+    let map = session_state();
+    let mut group = c.benchmark_group("state map to struct");
+
+    group.bench_function("clone + from_value", |b| {
+        b.iter(|| Session {
+            title: cloned(black_box(&map), "title"),
+            volume: cloned(black_box(&map), "volume"),
+            recent_files: cloned(black_box(&map), "recent_files"),
+            waveform: cloned(black_box(&map), "waveform"),
+        })
+    });
+    group.bench_function("to_typed (borrowed)", |b| b.iter(|| black_box(&map).to_typed().unwrap()));
+    group.bench_function("into_typed (moved)", |b| {
+        b.iter_batched(|| map.clone(), |map| map.into_typed().unwrap(), BatchSize::LargeInput)
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, conversion);
+criterion_main!(benches);
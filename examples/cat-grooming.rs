@@ -0,0 +1,53 @@
+use map_to_struct::{MapToStruct, MapToStructError, StateMap};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use specta::Type;
+
+// ---------------------------------------------------------------------
+// 1️⃣  The state map: a HashMap<String, Value> that knows its record type
+// ---------------------------------------------------------------------
+// (`to_typed` / `to_typed_report` / `from_typed` and `specta::Type` come
+// from `StateMap` once the record derives `MapToStruct`)
+pub type GroomingStateMap = StateMap<GroomingRecord>;
+
+// ---------------------------------------------------------------------
+// 2️⃣  The grooming record (5 cat‑related fields)
+// ---------------------------------------------------------------------
+#[derive(Debug, Clone, Serialize, Deserialize, Type, MapToStruct)]
+pub struct GroomingRecord {
+    pub fur_length_cm: i32,   // measured length of the cat’s fur
+    #[map_to_struct(one_of("slicker", "pin", "metal"))]
+    pub brush_type: String,   // e.g. “slicker”, “pin”, “metal”
+    #[map_to_struct(range(0..=10), default = 0)]
+    pub shedding_score: u8,   // 0‑10 rating of how much hair is shedding
+    #[map_to_struct(default)]
+    pub nail_trimmed: bool,   // was the nail trimming done?
+    #[map_to_struct(non_empty)]
+    pub favorite_spot: String,// where the cat likes to be groomed
+}
+
+// ---------------------------------------------------------------------
+// 3️⃣  A map populated with the 5 cat‑grooming keys
+// ---------------------------------------------------------------------
+pub fn grooming_state() -> GroomingStateMap {
+    let mut map = GroomingStateMap::new();
+    map.set("fur_length_cm", json!(2));               // centimeters
+    map.set("brush_type", json!("slicker"));
+    map.set("shedding_score", json!(7));             // 0‑10
+    map.set("nail_trimmed", json!(true));
+    map.set("favorite_spot", json!("chin"));
+    map
+}
+
+// ---------------------------------------------------------------------
+// 4️⃣  Round trip: map → struct → map
+// ---------------------------------------------------------------------
+fn main() -> Result<(), MapToStructError> {
+    let map = grooming_state();
+    let record = map.to_typed()?;
+    println!("{:#?}", record);
+
+    let back = GroomingStateMap::from(record);
+    println!("{}", serde_json::to_string_pretty(&back).expect("map serializes"));
+    Ok(())
+}
@@ -0,0 +1,324 @@
+use proc_macro2::{Span, TokenStream};
+use quote::ToTokens;
+use syn::ext::IdentExt;
+use syn::meta::ParseNestedMeta;
+use syn::parse::Parse;
+use syn::parse::Parser as _;
+use syn::{Attribute, Expr, Ident, LitInt, LitStr, Member, Path, Token, Type, Visibility};
+
+use crate::case::RenameRule;
+
+/// Everything the expansion needs, whichever macro it came from.
+pub(crate) struct Container {
+    pub krate: Path,
+    pub vis: Visibility,
+    pub ident: Ident,
+    pub map: Option<MapType>,
+    pub specta: SpectaMode,
+    pub deny_unknown_keys: bool,
+    pub version: Option<Version>,
+    pub fields: Vec<Field>,
+}
+
+/// `version = N`, and the `migrate = ..` function that upgrades older maps.
+pub(crate) struct Version {
+    pub number: u32,
+    pub migrate: Option<Path>,
+}
+
+/// The wrapper named by `map = ..`, and which of its fields holds the entries.
+pub(crate) struct MapType {
+    pub ty: Type,
+    pub field: Member,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SpectaMode {
+    Partial,
+    Record,
+}
+
+pub(crate) struct Field {
+    pub vis: Visibility,
+    pub ident: Ident,
+    pub ty: Type,
+    pub key: String,
+    pub aliases: Vec<LitStr>,
+    pub default: Option<FieldDefault>,
+    pub nested: bool,
+    pub rest: bool,
+    pub coerce: bool,
+    pub checks: Vec<Check>,
+}
+
+impl Container {
+    /// Fields read from their own key, i.e. all but the `rest` field.
+    pub(crate) fn keyed_fields(&self) -> impl Iterator<Item = &Field> {
+        self.fields.iter().filter(|field| !field.rest)
+    }
+
+    pub(crate) fn has_rest(&self) -> bool {
+        self.fields.iter().any(|field| field.rest)
+    }
+}
+
+pub(crate) enum FieldDefault {
+    Trait,
+    Expr(Expr),
+}
+
+pub(crate) enum Check {
+    Range(Expr),
+    NonEmpty,
+    OneOf(Vec<Expr>),
+    Validate(Path),
+}
+
+/// Bare attributes `map_to_struct!` accepts as shorthand for `#[map_to_struct(..)]`.
+const SHORT_CONTAINER_ATTRS: &[&str] =
+    &["rename_all", "specta", "field", "strict", "deny_unknown_keys", "version", "migrate", "coerce"];
+const SHORT_FIELD_ATTRS: &[&str] =
+    &["default", "key", "alias", "nested", "rest", "coerce", "range", "non_empty", "one_of", "validate"];
+
+pub(crate) struct ContainerOptions {
+    pub krate: Path,
+    pub map: Option<Type>,
+    pub field: Option<Member>,
+    pub rename_all: RenameRule,
+    pub specta: SpectaMode,
+    pub deny_unknown_keys: bool,
+    pub version: Option<LitInt>,
+    pub migrate: Option<Path>,
+    pub coerce: bool,
+}
+
+impl Default for ContainerOptions {
+    fn default() -> Self {
+        Self {
+            krate: syn::parse_quote!(::map_to_struct),
+            map: None,
+            field: None,
+            rename_all: RenameRule::None,
+            specta: SpectaMode::Partial,
+            deny_unknown_keys: false,
+            version: None,
+            migrate: None,
+            coerce: false,
+        }
+    }
+}
+
+impl ContainerOptions {
+    /// Applies `#[map_to_struct(..)]` (and, when `short` is set, the bare
+    /// forms) from `attrs`, returning the attributes that aren't ours.
+    pub(crate) fn parse_attrs(&mut self, attrs: Vec<Attribute>, short: bool) -> syn::Result<Vec<Attribute>> {
+        filter_attrs(attrs, short.then_some(SHORT_CONTAINER_ATTRS), |meta| self.apply(meta))
+    }
+
+    fn apply(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("map") {
+            self.map = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("field") {
+            self.field = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("crate") {
+            self.krate = meta.value()?.parse()?;
+        } else if meta.path.is_ident("rename_all") {
+            let rule: LitStr = meta.value()?.parse()?;
+            self.rename_all = RenameRule::from_str(&rule.value()).map_err(|e| syn::Error::new(rule.span(), e))?;
+        } else if meta.path.is_ident("deny_unknown_keys") || meta.path.is_ident("strict") {
+            self.deny_unknown_keys = true;
+        } else if meta.path.is_ident("coerce") {
+            self.coerce = true;
+        } else if meta.path.is_ident("version") {
+            self.version = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("migrate") {
+            self.migrate = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("specta") {
+            let mode: LitStr = meta.value()?.parse()?;
+            self.specta = match mode.value().as_str() {
+                "partial" => SpectaMode::Partial,
+                "record" => SpectaMode::Record,
+                other => {
+                    let msg = format!("unknown specta mode {:?}, expected \"partial\" or \"record\"", other);
+                    return Err(syn::Error::new(mode.span(), msg));
+                }
+            };
+        } else {
+            return Err(meta.error(format!("unknown map_to_struct option `{}`", path_name(&meta.path))));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn into_container(self, vis: Visibility, ident: Ident, mut fields: Vec<Field>) -> syn::Result<Container> {
+        let map = match (self.map, self.field) {
+            (Some(ty), field) => Some(MapType { ty, field: field.unwrap_or_else(|| Member::from(0)) }),
+            (None, Some(field)) => return Err(syn::Error::new_spanned(field, "`field` needs a `map` type")),
+            (None, None) => None,
+        };
+        let mut rest = fields.iter().filter(|field| field.rest);
+        if let (Some(_), Some(second)) = (rest.next(), rest.next()) {
+            return Err(syn::Error::new(second.ident.span(), "only one field can be marked rest"));
+        }
+        if self.deny_unknown_keys && fields.iter().any(|field| field.rest) {
+            return Err(syn::Error::new(ident.span(), "deny_unknown_keys has nothing to reject with a rest field"));
+        }
+        // Nested structs coerce by their own setting, and the rest is kept as is
+        for field in fields.iter_mut().filter(|field| !field.nested && !field.rest) {
+            field.coerce |= self.coerce;
+        }
+        let version = match (self.version, self.migrate) {
+            (Some(lit), migrate) => {
+                let number = lit.base10_parse()?;
+                if number == 0 {
+                    return Err(syn::Error::new(lit.span(), "versions start at 1; unversioned maps count as 0"));
+                }
+                Some(Version { number, migrate })
+            }
+            (None, Some(migrate)) => return Err(syn::Error::new_spanned(migrate, "`migrate` needs a `version`")),
+            (None, None) => None,
+        };
+        Ok(Container {
+            krate: self.krate,
+            vis,
+            ident,
+            map,
+            specta: self.specta,
+            deny_unknown_keys: self.deny_unknown_keys,
+            version,
+            fields,
+        })
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct FieldOptions {
+    key: Option<LitStr>,
+    aliases: Vec<LitStr>,
+    default: Option<FieldDefault>,
+    nested: bool,
+    rest: bool,
+    coerce: bool,
+    checks: Vec<Check>,
+}
+
+impl FieldOptions {
+    pub(crate) fn parse_attrs(&mut self, attrs: Vec<Attribute>, short: bool) -> syn::Result<Vec<Attribute>> {
+        filter_attrs(attrs, short.then_some(SHORT_FIELD_ATTRS), |meta| self.apply(meta))
+    }
+
+    /// `field: Type = expr` in `map_to_struct!`
+    pub(crate) fn set_default_expr(&mut self, expr: Expr, span: Span) -> syn::Result<()> {
+        self.set_default(FieldDefault::Expr(expr), span)
+    }
+
+    fn set_default(&mut self, default: FieldDefault, span: Span) -> syn::Result<()> {
+        if self.default.is_some() {
+            return Err(syn::Error::new(span, "duplicate default for this field"));
+        }
+        self.default = Some(default);
+        Ok(())
+    }
+
+    fn apply(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        let span = meta.path.get_ident().map_or_else(Span::call_site, Ident::span);
+        if meta.path.is_ident("default") {
+            let default = if meta.input.peek(Token![=]) {
+                FieldDefault::Expr(meta.value()?.parse()?)
+            } else {
+                FieldDefault::Trait
+            };
+            self.set_default(default, span)?;
+        } else if meta.path.is_ident("key") {
+            if self.key.is_some() {
+                return Err(meta.error("duplicate key for this field"));
+            }
+            self.key = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("alias") {
+            self.aliases.push(meta.value()?.parse()?);
+        } else if meta.path.is_ident("nested") {
+            self.nested = true;
+        } else if meta.path.is_ident("rest") {
+            self.rest = true;
+        } else if meta.path.is_ident("coerce") {
+            self.coerce = true;
+        } else if meta.path.is_ident("range") {
+            let content;
+            syn::parenthesized!(content in meta.input);
+            self.checks.push(Check::Range(content.parse()?));
+        } else if meta.path.is_ident("non_empty") {
+            self.checks.push(Check::NonEmpty);
+        } else if meta.path.is_ident("one_of") {
+            let content;
+            syn::parenthesized!(content in meta.input);
+            let allowed = content.parse_terminated(Expr::parse, Token![,])?;
+            self.checks.push(Check::OneOf(allowed.into_iter().collect()));
+        } else if meta.path.is_ident("validate") {
+            let content;
+            syn::parenthesized!(content in meta.input);
+            self.checks.push(Check::Validate(content.parse()?));
+        } else {
+            return Err(meta.error(format!("unknown map_to_struct field attribute `{}`", path_name(&meta.path))));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn into_field(
+        self,
+        vis: Visibility,
+        ident: Ident,
+        ty: Type,
+        rename_all: RenameRule,
+    ) -> syn::Result<Field> {
+        if self.nested && (self.default.is_some() || self.coerce || !self.checks.is_empty()) {
+            return Err(syn::Error::new(
+                ident.span(),
+                "nested fields take their defaults, coercion and validation from the nested struct",
+            ));
+        }
+        let keyed = self.nested || self.key.is_some() || !self.aliases.is_empty();
+        if self.rest && (keyed || self.default.is_some() || self.coerce || !self.checks.is_empty()) {
+            return Err(syn::Error::new(ident.span(), "a rest field takes every unread key and no other options"));
+        }
+        let key = match &self.key {
+            Some(key) => key.value(),
+            None => rename_all.apply(&ident.unraw().to_string()),
+        };
+        Ok(Field {
+            vis,
+            ident,
+            ty,
+            key,
+            aliases: self.aliases,
+            default: self.default,
+            nested: self.nested,
+            rest: self.rest,
+            coerce: self.coerce,
+            checks: self.checks,
+        })
+    }
+}
+
+// Runs `apply` on every option in our attributes and hands the rest back
+fn filter_attrs(
+    attrs: Vec<Attribute>,
+    short: Option<&[&str]>,
+    mut apply: impl FnMut(ParseNestedMeta) -> syn::Result<()>,
+) -> syn::Result<Vec<Attribute>> {
+    let mut rest = Vec::new();
+    for attr in attrs {
+        if attr.path().is_ident("map_to_struct") {
+            attr.parse_nested_meta(&mut apply)?;
+        } else if short.is_some_and(|names| names.iter().any(|name| attr.path().is_ident(name))) {
+            // `#[range(0..=10)]` parses just like `range(0..=10)` inside `#[map_to_struct(..)]`
+            let tokens: TokenStream = attr.meta.to_token_stream();
+            syn::meta::parser(&mut apply).parse2(tokens)?;
+        } else {
+            rest.push(attr);
+        }
+    }
+    Ok(rest)
+}
+
+fn path_name(path: &Path) -> String {
+    path.to_token_stream().to_string().replace(' ', "")
+}
@@ -0,0 +1,93 @@
+// `rename_all` conventions, as in serde, applied to snake_case field names
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum RenameRule {
+    #[default]
+    None,
+    LowerCase,
+    UpperCase,
+    PascalCase,
+    CamelCase,
+    SnakeCase,
+    ScreamingSnakeCase,
+    KebabCase,
+    ScreamingKebabCase,
+}
+
+const RULES: &[(&str, RenameRule)] = &[
+    ("lowercase", RenameRule::LowerCase),
+    ("UPPERCASE", RenameRule::UpperCase),
+    ("PascalCase", RenameRule::PascalCase),
+    ("camelCase", RenameRule::CamelCase),
+    ("snake_case", RenameRule::SnakeCase),
+    ("SCREAMING_SNAKE_CASE", RenameRule::ScreamingSnakeCase),
+    ("kebab-case", RenameRule::KebabCase),
+    ("SCREAMING-KEBAB-CASE", RenameRule::ScreamingKebabCase),
+];
+
+impl RenameRule {
+    pub(crate) fn from_str(rule: &str) -> Result<Self, String> {
+        RULES.iter().find(|(name, _)| *name == rule).map(|(_, rule)| *rule).ok_or_else(|| {
+            let names: Vec<_> = RULES.iter().map(|(name, _)| format!("{:?}", name)).collect();
+            format!("unknown rename_all rule {:?}, expected one of {}", rule, names.join(", "))
+        })
+    }
+
+    pub(crate) fn apply(self, field: &str) -> String {
+        match self {
+            Self::None | Self::LowerCase | Self::SnakeCase => field.to_string(),
+            Self::UpperCase | Self::ScreamingSnakeCase => field.to_ascii_uppercase(),
+            Self::PascalCase => {
+                let mut pascal = String::with_capacity(field.len());
+                let mut capitalize = true;
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(ch);
+                    }
+                }
+                pascal
+            }
+            Self::CamelCase => {
+                let pascal = Self::PascalCase.apply(field);
+                let mut chars = pascal.chars();
+                chars.next().map(|first| first.to_ascii_lowercase().to_string() + chars.as_str()).unwrap_or_default()
+            }
+            Self::KebabCase => field.replace('_', "-"),
+            Self::ScreamingKebabCase => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RenameRule, RULES};
+
+    #[test]
+    fn rules_rename_snake_case_fields() {
+        let renamed: Vec<_> = RULES.iter().map(|(_, rule)| rule.apply("fur_length_cm")).collect();
+        assert_eq!(renamed, [
+            "fur_length_cm",
+            "FUR_LENGTH_CM",
+            "FurLengthCm",
+            "furLengthCm",
+            "fur_length_cm",
+            "FUR_LENGTH_CM",
+            "fur-length-cm",
+            "FUR-LENGTH-CM",
+        ]);
+        assert_eq!(RenameRule::None.apply("r#type"), "r#type");
+        // Only whole characters are recased
+        assert_eq!(RenameRule::CamelCase.apply("ärger_level"), "ärgerLevel");
+    }
+
+    #[test]
+    fn rules_are_parsed_by_their_serde_names() {
+        assert_eq!(RenameRule::from_str("kebab-case"), Ok(RenameRule::KebabCase));
+        assert!(RenameRule::from_str("kebab").unwrap_err().starts_with("unknown rename_all rule \"kebab\""));
+    }
+}
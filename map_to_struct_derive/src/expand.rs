@@ -0,0 +1,1162 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::{Ident, Member};
+
+use crate::attr::{Check, Container, Field, FieldDefault, MapType, SpectaMode};
+use crate::case::RenameRule;
+
+pub(crate) fn expand(container: &Container) -> TokenStream {
+    let krate = &container.krate;
+    let ident = &container.ident;
+
+    // Locals are positional so field names can't shadow the parameters
+    let locals: Vec<_> = (0..container.fields.len()).map(|i| format_ident!("__field{}", i)).collect();
+    let names: Vec<_> = container.fields.iter().map(|f| &f.ident).collect();
+
+    let mut known: Vec<_> = container
+        .keyed_fields()
+        .map(|field| {
+            let key = &field.key;
+            let aliases = &field.aliases;
+            if field.nested {
+                let ty = &field.ty;
+                quote_spanned! {at(ty)=>
+                    __key == #key
+                        || __key
+                            .strip_prefix(::core::concat!(#key, "."))
+                            .is_some_and(<#ty as #krate::MapStruct>::is_known_key)
+                }
+            } else {
+                quote! { ::core::matches!(__key, #key #(| #aliases)*) }
+            }
+        })
+        .collect();
+    let key_ident = key_ident(container);
+    let value_ident = value_ident(container);
+    let variants: Vec<_> = container.keyed_fields().map(key_variant).collect();
+    let key_of = known.iter().zip(&variants).map(|(known, variant)| {
+        quote! {
+            if #known {
+                return ::core::option::Option::Some(#key_ident::#variant);
+            }
+        }
+    });
+    let key_of = quote! {
+        #(#key_of)*
+        ::core::option::Option::None
+    };
+    if container.version.is_some() {
+        known.push(quote! { __key == #krate::VERSION_KEY });
+    }
+    // Everything the other fields don't read, for a `rest` field
+    let rest = quote! {
+        #krate::__private::extract_rest(__source, |__key: &str| #(#known ||)* false)
+    };
+
+    let take = container.fields.iter().zip(&locals).map(|(field, local)| {
+        let value = if field.rest {
+            return quote! {
+                let #local = #krate::__private::take_rest(__map, |__key: &str| #(#known ||)* false);
+            };
+        } else if field.nested {
+            let (key, ty) = (&field.key, &field.ty);
+            quote_spanned! {at(ty)=> #krate::__private::take_nested::<#ty>(__map, #key) }
+        } else {
+            take_field_expr(krate, field)
+        };
+        quote! { let #local = #value?; }
+    });
+
+    let extract = container.fields.iter().zip(&locals).map(|(field, local)| {
+        if field.rest {
+            return quote! { let #local = #rest; };
+        }
+        let value = if field.nested {
+            nested_expr(krate, field)
+        } else {
+            field_expr(krate, field)
+        };
+        quote! { let #local = #value?; }
+    });
+
+    let record = container.fields.iter().zip(&locals).map(|(field, local)| {
+        let key = &field.key;
+        let ty = &field.ty;
+        if field.rest {
+            quote! { let #local = ::core::option::Option::Some(#rest); }
+        } else if field.nested {
+            quote_spanned! {at(ty)=>
+                let #local = __report.record_nested(
+                    #key,
+                    #krate::__private::nested_source(__source, #key)
+                        .map(|nested| <#ty as #krate::MapStruct>::extract_report(&nested)),
+                );
+            }
+        } else {
+            let value = field_expr(krate, field);
+            let coerced = field.coerce.then(|| {
+                let aliases = &field.aliases;
+                quote_spanned! {at(ty)=>
+                    if #local.is_some() && #krate::__private::is_coerced::<#ty>(__source, #key, &[#(#aliases),*]) {
+                        __report.coerced.push(::std::string::ToString::to_string(#key));
+                    }
+                }
+            });
+            quote! {
+                let #local = __report.record(#key, #value);
+                #coerced
+            }
+        }
+    });
+
+    let complete = if locals.is_empty() {
+        quote! { __report.value = ::core::option::Option::Some(Self {}); }
+    } else {
+        quote! {
+            if let (#(::core::option::Option::Some(#locals),)*) = (#(#locals,)*) {
+                __report.value = ::core::option::Option::Some(Self { #(#names: #locals),* });
+            }
+        }
+    };
+
+    // The rest goes first so a stray entry can't overwrite a field's key
+    let insert_rest = container.fields.iter().filter(|field| field.rest).map(|field| {
+        let name = &field.ident;
+        quote! { #krate::__private::insert_rest(__map, __prefix, &self.#name); }
+    });
+    let insert = container.keyed_fields().map(|field| {
+        let key = &field.key;
+        let name = &field.ident;
+        if field.nested {
+            quote! {
+                #krate::MapStruct::try_insert_fields(
+                    &self.#name,
+                    __map,
+                    &::std::format!("{}{}.", __prefix, #key),
+                )?;
+            }
+        } else {
+            quote! {
+                #krate::__private::insert_field(
+                    __map,
+                    &#krate::__private::prefixed_key(__prefix, #key),
+                    &self.#name,
+                )?;
+            }
+        }
+    });
+
+    let version = container.version.as_ref().map(|version| {
+        let number = version.number;
+        let migrate = version.migrate.as_ref().map(|path| {
+            quote_spanned! {at(path)=>
+                fn migrate(__from: u32, __map: &mut dyn #krate::ValueMap) {
+                    #path(__from, __map)
+                }
+            }
+        });
+        quote! {
+            const VERSION: ::core::option::Option<u32> = ::core::option::Option::Some(#number);
+
+            #migrate
+        }
+    });
+
+    let read_value = container.keyed_fields().zip(&variants).map(|(field, variant)| {
+        let value = if field.nested {
+            nested_expr(krate, field)
+        } else {
+            field_expr(krate, field)
+        };
+        quote! { #key_ident::#variant => #value.map(#value_ident::#variant), }
+    });
+    let write_value = container.keyed_fields().zip(&variants).map(|(field, variant)| {
+        let key = &field.key;
+        let write = if field.nested {
+            quote! {
+                #krate::ValueMap::remove(__map, #key);
+                #krate::MapStruct::insert_fields(__value, __map, &::std::format!("{}.", #key));
+            }
+        } else {
+            quote! { #krate::__private::write_field(__map, #key, __value); }
+        };
+        quote! { #value_ident::#variant(ref __value) => { #write } }
+    });
+
+    let diff = container.keyed_fields().map(|field| {
+        let key = &field.key;
+        if field.nested {
+            let ty = &field.ty;
+            quote_spanned! {at(ty)=>
+                #krate::__private::diff_nested::<#ty>(__old, __new, __prefix, #key, __changes);
+            }
+        } else {
+            let aliases = &field.aliases;
+            quote! {
+                #krate::__private::diff_field(__old, __new, __prefix, #key, &[#(#aliases),*], __changes);
+            }
+        }
+    });
+
+    let map_type = map_type(container);
+    let map_schema = map_schema(container);
+    let title = container.ident.unraw().to_string();
+    let typescript_fields = typescript_fields(container);
+    // Only typed fields register anything
+    let types = match container.specta {
+        SpectaMode::Partial => quote! { let mut __types = #krate::__private::specta::TypeCollection::default(); },
+        SpectaMode::Record => quote! { let __types = #krate::__private::specta::TypeCollection::default(); },
+    };
+    let deny_unknown_keys = container.deny_unknown_keys;
+    // With a rest field every key is read by something
+    let is_known = if container.has_rest() {
+        quote! { let _ = __key; true }
+    } else {
+        quote! { #(#known ||)* false }
+    };
+    let unknown_source_keys = unknown_source_keys(container);
+    let apply_patch = apply_patch(container);
+    let patch_keys = patch_keys(container);
+    let key_enum = key_enum(container);
+    let value_enum = value_enum(container);
+    let patch_struct = patch_struct(container);
+    let patch_ident = patch_ident(container);
+    let map_impls = match &container.map {
+        Some(map) => map_impls(container, map),
+        None => state_map_accessors(container),
+    };
+
+    quote! {
+        #[automatically_derived]
+        impl #krate::MapStruct for #ident {
+            type Key = #key_ident;
+            type Patch = #patch_ident;
+            type Value = #value_ident;
+
+            const DENY_UNKNOWN_KEYS: bool = #deny_unknown_keys;
+
+            #version
+
+            fn extract(
+                __source: &dyn #krate::ValueSource,
+            ) -> ::core::result::Result<Self, #krate::MapToStructError> {
+                #(#extract)*
+                ::core::result::Result::Ok(Self { #(#names: #locals),* })
+            }
+
+            fn take(
+                __map: &mut dyn #krate::ValueMap,
+            ) -> ::core::result::Result<Self, #krate::MapToStructError> {
+                #(#take)*
+                ::core::result::Result::Ok(Self { #(#names: #locals),* })
+            }
+
+            fn is_known_key(__key: &str) -> bool {
+                #is_known
+            }
+
+            fn unknown_source_keys(
+                __source: &dyn #krate::ValueSource,
+                __prefix: &str,
+                __unknown: &mut ::std::vec::Vec<::std::string::String>,
+            ) {
+                #unknown_source_keys
+            }
+
+            fn key_of(__key: &str) -> ::core::option::Option<Self::Key> {
+                #key_of
+            }
+
+            fn read_value(
+                __source: &dyn #krate::ValueSource,
+                __key: Self::Key,
+            ) -> ::core::result::Result<Self::Value, #krate::MapToStructError> {
+                match __key {
+                    #(#read_value)*
+                }
+            }
+
+            fn write_value(__map: &mut dyn #krate::ValueMap, __value: &Self::Value) {
+                match *__value {
+                    #(#write_value)*
+                }
+            }
+
+            fn value_key(__value: &Self::Value) -> Self::Key {
+                __value.key()
+            }
+
+            fn extract_report(__source: &dyn #krate::ValueSource) -> #krate::MapToStructReport<Self> {
+                let mut __report = #krate::MapToStructReport::default();
+                #(#record)*
+                #complete
+                __report
+            }
+
+            fn try_insert_fields(
+                &self,
+                __map: &mut dyn #krate::ValueMap,
+                __prefix: &str,
+            ) -> ::core::result::Result<(), #krate::MapToStructError> {
+                #(#insert_rest)*
+                #(#insert)*
+                ::core::result::Result::Ok(())
+            }
+
+            fn diff_sources(
+                __old: &dyn #krate::ValueSource,
+                __new: &dyn #krate::ValueSource,
+                __prefix: &str,
+                __changes: &mut ::std::vec::Vec<#krate::FieldChange>,
+            ) {
+                #(#diff)*
+            }
+
+            fn apply_patch(
+                __map: &mut dyn #krate::ValueMap,
+                __patch: Self::Patch,
+            ) -> ::core::result::Result<::std::vec::Vec<Self::Key>, #krate::MapToStructError> {
+                #apply_patch
+            }
+
+            fn patch_keys(__patch: &Self::Patch) -> ::std::vec::Vec<Self::Key> {
+                #patch_keys
+            }
+
+            fn map_schema(__builder: &mut #krate::SchemaBuilder) -> #krate::MapSchema {
+                #map_schema
+            }
+
+            fn typescript(
+                __config: &#krate::specta_typescript::Typescript,
+            ) -> ::core::result::Result<::std::string::String, #krate::specta_typescript::ExportError> {
+                #types
+                let __fields = ::std::vec![#(#typescript_fields),*];
+                #krate::__private::typescript_module(__config, #title, &__types, __fields)
+            }
+
+            fn map_type(
+                type_map: &mut #krate::__private::specta::TypeCollection,
+                generics: #krate::__private::specta::Generics,
+            ) -> #krate::__private::specta::datatype::DataType {
+                #map_type
+            }
+        }
+
+        #key_enum
+
+        #value_enum
+
+        #patch_struct
+
+        #map_impls
+    }
+}
+
+// `Result<T, MapToStructError>` for a plain field, validated if it has checks
+fn field_expr(krate: &syn::Path, field: &Field) -> TokenStream {
+    let read = if field.coerce { quote!(extract_coerced_or) } else { quote!(extract_field_or) };
+    read_field(krate, field, read, quote!(__source))
+}
+
+// Body of `MapStruct::unknown_source_keys`: keys no plain field reads, then
+// whatever each nested field finds unknown in its own entries
+fn unknown_source_keys(container: &Container) -> TokenStream {
+    let krate = &container.krate;
+    if container.has_rest() {
+        return quote! { let _ = (__source, __prefix, __unknown); };
+    }
+
+    let mut own: Vec<_> = container
+        .keyed_fields()
+        .map(|field| {
+            let key = &field.key;
+            if field.nested {
+                quote! { __key == #key || __key.starts_with(::core::concat!(#key, ".")) }
+            } else {
+                let aliases = &field.aliases;
+                quote! { ::core::matches!(__key, #key #(| #aliases)*) }
+            }
+        })
+        .collect();
+    if container.version.is_some() {
+        own.push(quote! { __key == #krate::VERSION_KEY });
+    }
+    let nested = container.keyed_fields().filter(|field| field.nested).map(|field| {
+        let (key, ty) = (&field.key, &field.ty);
+        quote_spanned! {at(ty)=>
+            #krate::__private::unknown_nested::<#ty>(__source, __prefix, #key, __unknown);
+        }
+    });
+
+    quote! {
+        for __key in #krate::ValueSource::source_keys(__source) {
+            if !(#(#own ||)* false) {
+                __unknown.push(#krate::__private::prefixed_key(__prefix, __key).into_owned());
+            }
+        }
+        #(#nested)*
+    }
+}
+
+// `field_expr`, moving the value out of `__map`
+fn take_field_expr(krate: &syn::Path, field: &Field) -> TokenStream {
+    let read = if field.coerce { quote!(take_coerced_or) } else { quote!(take_field_or) };
+    read_field(krate, field, read, quote!(__map))
+}
+
+fn read_field(krate: &syn::Path, field: &Field, read: TokenStream, from: TokenStream) -> TokenStream {
+    let key = &field.key;
+    let ty = &field.ty;
+    let aliases = &field.aliases;
+    let missing = match &field.default {
+        None => quote! { ::core::option::Option::None },
+        Some(FieldDefault::Trait) => quote_spanned! {at(ty)=>
+            ::core::option::Option::Some(<#ty as ::core::default::Default>::default())
+        },
+        Some(FieldDefault::Expr(expr)) => quote! { ::core::option::Option::Some(#expr) },
+    };
+    let extract = quote_spanned! {at(ty)=>
+        #krate::__private::#read::<#ty>(#from, #key, &[#(#aliases),*], || #missing)
+    };
+    if field.checks.is_empty() {
+        return extract;
+    }
+
+    let checks = field.checks.iter().map(|check| match check {
+        Check::Range(range) => quote_spanned! {at(range)=>
+            #krate::__private::check_range(#key, __value, #range)?;
+        },
+        Check::NonEmpty => quote_spanned! {at(ty)=>
+            #krate::__private::check_non_empty(#key, __value)?;
+        },
+        Check::OneOf(allowed) => quote! {
+            #krate::__private::check_one_of(#key, __value, &[#(#allowed),*])?;
+        },
+        Check::Validate(path) => quote_spanned! {at(path)=>
+            #krate::__private::check_with(#key, __value, #path)?;
+        },
+    });
+    quote! {
+        #krate::__private::validate_field(#extract, |__value: &#ty| {
+            #(#checks)*
+            ::core::result::Result::Ok(())
+        })
+    }
+}
+
+fn nested_expr(krate: &syn::Path, field: &Field) -> TokenStream {
+    let key = &field.key;
+    let ty = &field.ty;
+    quote_spanned! {at(ty)=>
+        #krate::__private::extract_nested::<#ty>(__source, #key)
+    }
+}
+
+// Body of `MapStruct::map_type`: every key optional, or `Record<string, unknown>`
+fn map_type(container: &Container) -> TokenStream {
+    let krate = &container.krate;
+    match container.specta {
+        SpectaMode::Partial => {
+            let name = format!("{}Map", container.ident.unraw());
+            let mut fields = field_types(container);
+            if container.version.is_some() {
+                fields.push(quote! {
+                    (
+                        ::std::borrow::Cow::Borrowed(#krate::VERSION_KEY),
+                        <u32 as #krate::__private::specta::Type>::reference(type_map, &[]).inner,
+                    )
+                });
+            }
+            quote! {
+                let _ = generics;
+                #krate::__private::partial_object(#name, ::std::vec![#(#fields),*])
+            }
+        }
+        SpectaMode::Record => quote! { #krate::__private::record_type(type_map, generics) },
+    }
+}
+
+// `(key, DataType)` for every keyed field, registering named types in
+// `type_map`; `unknown` with `specta = "record"`
+fn field_types(container: &Container) -> Vec<TokenStream> {
+    let krate = &container.krate;
+    container
+        .keyed_fields()
+        .map(|field| {
+            let key = &field.key;
+            let ty = &field.ty;
+            match container.specta {
+                SpectaMode::Partial => quote_spanned! {at(ty)=>
+                    (
+                        ::std::borrow::Cow::Borrowed(#key),
+                        <#ty as #krate::__private::specta::Type>::reference(type_map, &[]).inner,
+                    )
+                },
+                SpectaMode::Record => quote! {
+                    (
+                        ::std::borrow::Cow::Borrowed(#key),
+                        #krate::__private::specta::datatype::DataType::Unknown,
+                    )
+                },
+            }
+        })
+        .collect()
+}
+
+// `(key, DataType, required)` for the TypeScript record, keyed like the
+// `…Key` enum: a nested field is one key holding its object, as `get`/`set`
+// commands reading and writing the `…Value` enum exchange it
+fn typescript_fields(container: &Container) -> Vec<TokenStream> {
+    let krate = &container.krate;
+    container
+        .keyed_fields()
+        .map(|field| {
+            let key = &field.key;
+            let ty = &field.ty;
+            let data_type = match container.specta {
+                SpectaMode::Partial => quote_spanned! {at(ty)=>
+                    <#ty as #krate::__private::specta::Type>::reference(&mut __types, &[]).inner
+                },
+                SpectaMode::Record => quote! { #krate::__private::specta::datatype::DataType::Unknown },
+            };
+            // A nested struct is optional when all of its own fields are
+            let required = match &field.default {
+                Some(_) => quote!(false),
+                None if field.nested => quote_spanned! {at(ty)=>
+                    <#ty as #krate::MapStruct>::extract(&#krate::__private::serde_json::Map::new()).is_err()
+                },
+                None => quote_spanned! {at(ty)=> #krate::__private::is_required::<#ty>() },
+            };
+            quote! { (::std::borrow::Cow::Borrowed(#key), #data_type, #required) }
+        })
+        .collect()
+}
+
+// Body of `MapStruct::map_schema`: each key with its type, checks and default
+fn map_schema(container: &Container) -> TokenStream {
+    let krate = &container.krate;
+    let title = container.ident.unraw().to_string();
+
+    let fields = container.keyed_fields().map(|field| {
+        let key = &field.key;
+        let ty = &field.ty;
+        if field.nested {
+            return quote_spanned! {at(ty)=>
+                __schema.nested(#key, <#ty as #krate::MapStruct>::map_schema(__builder));
+            };
+        }
+        let aliases = &field.aliases;
+        let schema = match container.specta {
+            SpectaMode::Partial => quote_spanned! {at(ty)=> __builder.type_schema::<#ty>() },
+            SpectaMode::Record => quote! { __builder.any() },
+        };
+        let checks = field.checks.iter().filter_map(|check| match check {
+            Check::Range(range) => Some(quote_spanned! {at(range)=>
+                #krate::__private::schema_range(&mut __field, #range);
+            }),
+            Check::NonEmpty => Some(quote! { #krate::__private::schema_non_empty(&mut __field); }),
+            Check::OneOf(allowed) => Some(quote! {
+                #krate::__private::schema_one_of(&mut __field, &[#(#allowed),*]);
+            }),
+            Check::Validate(_) => None,
+        });
+        let (default, required) = match &field.default {
+            None => (None, quote_spanned! {at(ty)=> #krate::__private::is_required::<#ty>() }),
+            Some(default) => {
+                let value = match default {
+                    FieldDefault::Trait => quote_spanned! {at(ty)=> <#ty as ::core::default::Default>::default() },
+                    FieldDefault::Expr(expr) => quote! { #expr },
+                };
+                let default = quote! {
+                    let __default: #ty = #value;
+                    #krate::__private::schema_default(&mut __field, &__default);
+                };
+                (Some(default), quote!(false))
+            }
+        };
+        quote! {
+            {
+                let mut __field = #schema;
+                #(#checks)*
+                #default
+                __schema.field(#key, &[#(#aliases),*], __field, #required);
+            }
+        }
+    });
+    let version = container.version.as_ref().map(|version| {
+        let number = version.number;
+        quote! { __schema.version(#number); }
+    });
+    let deny_unknown_keys = container.deny_unknown_keys.then(|| quote! { __schema.deny_unknown_keys(); });
+
+    quote! {
+        let mut __schema = #krate::MapSchema::new(#title);
+        #(#fields)*
+        #version
+        #deny_unknown_keys
+        __schema
+    }
+}
+
+// Conversions on the map wrapper plus its `specta::Type` impl
+fn map_impls(container: &Container, map: &MapType) -> TokenStream {
+    let krate = &container.krate;
+    let ident = &container.ident;
+    let MapType { ty: map, field } = map;
+
+    // A tuple newtype is built around an empty map; any other wrapper must
+    // implement `Default` so the other fields have something to start from
+    let methods = accessors(container, quote!(pub)).into_iter().map(|a| {
+        let Accessor { getter, setter, get_body, set_body } = a;
+        quote! {
+            #getter {
+                let __source: &dyn #krate::ValueSource = &self.#field;
+                #get_body
+            }
+
+            #setter {
+                let __map: &mut dyn #krate::ValueMap = &mut self.#field;
+                #set_body
+            }
+        }
+    });
+
+    let empty = match field {
+        Member::Unnamed(index) if index.index == 0 => quote! { Self(::core::default::Default::default()) },
+        _ => quote! { <Self as ::core::default::Default>::default() },
+    };
+
+    quote! {
+        #[allow(dead_code)]
+        impl #map {
+            pub fn to_typed(&self) -> ::core::result::Result<#ident, #krate::MapToStructError> {
+                <#ident as #krate::MapStruct>::from_map(&self.#field)
+            }
+
+            pub fn to_typed_report(&self) -> #krate::MapToStructReport<#ident> {
+                <#ident as #krate::MapStruct>::from_map_report(&self.#field)
+            }
+
+            /// `to_typed` without copying: the values are moved out of the map.
+            pub fn into_typed(self) -> ::core::result::Result<#ident, #krate::MapToStructError> {
+                <#ident as #krate::MapStruct>::from_map_owned(self.#field)
+            }
+
+            /// Keys that no field reads, e.g. left behind by a rename.
+            pub fn unknown_keys(&self) -> ::std::vec::Vec<::std::string::String> {
+                <#ident as #krate::MapStruct>::unknown_keys(&self.#field)
+            }
+
+            /// Migrates the map in place to the struct's current version, so
+            /// the upgrade can be persisted. Returns whether anything ran.
+            pub fn upgrade(&mut self) -> ::core::result::Result<bool, #krate::MapToStructError> {
+                <#ident as #krate::MapStruct>::upgrade(&mut self.#field)
+            }
+
+            /// The keys whose values differ from `other`'s.
+            pub fn diff(&self, other: &Self) -> ::std::vec::Vec<#krate::FieldChange> {
+                <#ident as #krate::MapStruct>::diff_maps(&self.#field, &other.#field)
+            }
+
+            /// A JSON Schema (draft 2020-12) for the map.
+            pub fn json_schema() -> #krate::__private::Value {
+                <#ident as #krate::MapStruct>::json_schema()
+            }
+
+            pub fn from_typed(typed: &#ident) -> Self {
+                let mut map = #empty;
+                #krate::MapStruct::insert_map(typed, &mut map.#field);
+                map
+            }
+
+            /// Validates and writes the fields present in `patch`, returning
+            /// the keys that changed.
+            pub fn apply_patch(
+                &mut self,
+                patch: <#ident as #krate::MapStruct>::Patch,
+            ) -> ::core::result::Result<
+                ::std::vec::Vec<<#ident as #krate::MapStruct>::Key>,
+                #krate::MapToStructError,
+            > {
+                <#ident as #krate::MapStruct>::apply_patch(&mut self.#field, patch)
+            }
+
+            #(#methods)*
+        }
+
+        #[automatically_derived]
+        impl ::core::convert::From<#ident> for #map {
+            fn from(typed: #ident) -> Self {
+                Self::from_typed(&typed)
+            }
+        }
+
+        #[automatically_derived]
+        impl #krate::__private::specta::Type for #map {
+            fn inline(
+                type_map: &mut #krate::__private::specta::TypeCollection,
+                generics: #krate::__private::specta::Generics,
+            ) -> #krate::__private::specta::datatype::DataType {
+                <#ident as #krate::MapStruct>::map_type(type_map, generics)
+            }
+        }
+    }
+}
+
+// `StructKey`: one variant per field, serialized as the field's map key
+fn key_enum(container: &Container) -> TokenStream {
+    let krate = &container.krate;
+    let vis = &container.vis;
+    let ident = container.ident.unraw();
+    let key_ident = key_ident(container);
+    let doc = format!("The keys of `{}`, for commands that take a single key.", ident);
+
+    let keys: Vec<_> = container.keyed_fields().map(|field| &field.key).collect();
+    let variants: Vec<_> = container.keyed_fields().map(key_variant).collect();
+    let serde = quote!(#krate::__private::serde);
+
+    quote! {
+        #[doc = #doc]
+        #[derive(
+            ::core::clone::Clone,
+            ::core::marker::Copy,
+            ::core::fmt::Debug,
+            ::core::cmp::PartialEq,
+            ::core::cmp::Eq,
+            ::core::hash::Hash,
+            #krate::__private::specta::Type,
+        )]
+        #[specta(crate = #krate::__private::specta)]
+        #[allow(dead_code)]
+        #vis enum #key_ident {
+            #(#[serde(rename = #keys)] #variants,)*
+        }
+
+        #[allow(dead_code)]
+        impl #key_ident {
+            /// Every key, in field order.
+            pub const ALL: &'static [Self] = &[#(Self::#variants),*];
+
+            /// The key as it appears in the map.
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    #(Self::#variants => #keys,)*
+                }
+            }
+        }
+
+        #[automatically_derived]
+        impl ::core::fmt::Display for #key_ident {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        #[automatically_derived]
+        impl ::core::str::FromStr for #key_ident {
+            type Err = #krate::UnknownKey;
+
+            fn from_str(key: &str) -> ::core::result::Result<Self, Self::Err> {
+                match key {
+                    #(#keys => ::core::result::Result::Ok(Self::#variants),)*
+                    _ => ::core::result::Result::Err(#krate::UnknownKey { key: ::std::string::ToString::to_string(key) }),
+                }
+            }
+        }
+
+        #[automatically_derived]
+        impl #serde::Serialize for #key_ident {
+            fn serialize<S: #serde::Serializer>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        #[automatically_derived]
+        impl<'de> #serde::Deserialize<'de> for #key_ident {
+            fn deserialize<D: #serde::Deserializer<'de>>(deserializer: D) -> ::core::result::Result<Self, D::Error> {
+                let key = <::std::borrow::Cow<'de, str> as #serde::Deserialize>::deserialize(deserializer)?;
+                ::core::str::FromStr::from_str(&key)
+                    .map_err(|_| #serde::de::Error::unknown_variant(&key, &[#(#keys),*]))
+            }
+        }
+    }
+}
+
+fn key_ident(container: &Container) -> Ident {
+    format_ident!("{}Key", container.ident.unraw(), span = container.ident.span())
+}
+
+fn key_variant(field: &Field) -> Ident {
+    let name = RenameRule::PascalCase.apply(&field.ident.unraw().to_string());
+    format_ident!("{}", name, span = field.ident.span())
+}
+
+fn value_ident(container: &Container) -> Ident {
+    format_ident!("{}Value", container.ident.unraw(), span = container.ident.span())
+}
+
+// `StructValue`: one variant per field holding its typed value, what an
+// `ObservableMap` hands its subscribers
+fn value_enum(container: &Container) -> TokenStream {
+    let krate = &container.krate;
+    let vis = &container.vis;
+    let ident = container.ident.unraw();
+    let (key_ident, value_ident) = (key_ident(container), value_ident(container));
+    let doc = format!("A typed value of one of `{}`'s fields, tagged by the field.", ident);
+    let serde = quote!(#krate::__private::serde);
+
+    let variants: Vec<_> = container.keyed_fields().map(key_variant).collect();
+    let types = container.keyed_fields().map(|field| &field.ty);
+
+    // Serialized as it's stored in the map, so nested values use their keys
+    let serialize = container.keyed_fields().zip(&variants).map(|(field, variant)| {
+        let write = if field.nested {
+            quote! {
+                let mut __nested = #krate::__private::serde_json::Map::new();
+                #krate::MapStruct::try_insert_fields(__value, &mut __nested, "")
+                    .map_err(#serde::ser::Error::custom)?;
+                #serde::Serialize::serialize(&__nested, serializer)
+            }
+        } else {
+            quote! { #serde::Serialize::serialize(__value, serializer) }
+        };
+        quote! { Self::#variant(ref __value) => { #write } }
+    });
+
+    quote! {
+        #[doc = #doc]
+        #[allow(dead_code)]
+        #vis enum #value_ident {
+            #(#variants(#types),)*
+        }
+
+        #[allow(dead_code)]
+        impl #value_ident {
+            /// The key of the field the value belongs to.
+            pub fn key(&self) -> #key_ident {
+                match *self {
+                    #(Self::#variants(_) => #key_ident::#variants,)*
+                }
+            }
+        }
+
+        #[automatically_derived]
+        impl #serde::Serialize for #value_ident {
+            fn serialize<S: #serde::Serializer>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error> {
+                match *self {
+                    #(#serialize)*
+                }
+            }
+        }
+    }
+}
+
+fn patch_ident(container: &Container) -> Ident {
+    format_ident!("{}Patch", container.ident.unraw(), span = container.ident.span())
+}
+
+// `StructPatch`: every field optional, read from and written as the keys
+// present in a partial map
+fn patch_struct(container: &Container) -> TokenStream {
+    let krate = &container.krate;
+    let vis = &container.vis;
+    let ident = &container.ident;
+    let patch_ident = patch_ident(container);
+    let doc = format!(
+        "A partial update to `{}`: `None` fields are left alone by `apply_patch`.",
+        ident.unraw()
+    );
+    let serde = quote!(#krate::__private::serde);
+
+    let fields = container.keyed_fields().map(|field| {
+        let (vis, name, ty) = (&field.vis, &field.ident, &field.ty);
+        quote! { #vis #name: ::core::option::Option<#ty> }
+    });
+    let names: Vec<_> = container.keyed_fields().map(|f| &f.ident).collect();
+
+    // Absent and `null` keys both leave the field alone, except that `null`
+    // clears a field that takes it (`Option`, `Patch`)
+    let read = container.keyed_fields().map(|field| {
+        let (name, key, ty) = (&field.ident, &field.key, &field.ty);
+        let aliases = &field.aliases;
+        let value = if field.nested {
+            quote_spanned! {at(ty)=>
+                match #krate::ValueSource::get_value(__source, #key) {
+                    ::core::option::Option::None | ::core::option::Option::Some(#krate::__private::Value::Null) => {
+                        ::core::option::Option::None
+                    }
+                    ::core::option::Option::Some(_) => ::core::option::Option::Some(
+                        #krate::__private::extract_nested::<#ty>(__source, #key)
+                            .map_err(#serde::de::Error::custom)?,
+                    ),
+                }
+            }
+        } else {
+            let read = if field.coerce { quote!(extract_coerced_or) } else { quote!(extract_field_or) };
+            quote_spanned! {at(ty)=>
+                match #krate::__private::patch_null::<#ty>(__source, #key, &[#(#aliases),*]) {
+                    ::core::option::Option::Some(__null) => ::core::option::Option::Some(__null),
+                    ::core::option::Option::None => #krate::__private::#read::<::core::option::Option<#ty>>(
+                        __source, #key, &[#(#aliases),*], || ::core::option::Option::None,
+                    )
+                    .map_err(#serde::de::Error::custom)?,
+                }
+            }
+        };
+        quote! { #name: #value }
+    });
+
+    let write = container.keyed_fields().map(|field| {
+        let (name, key) = (&field.ident, &field.key);
+        if field.nested {
+            quote! {
+                if let ::core::option::Option::Some(__value) = &self.#name {
+                    let mut __nested = #krate::__private::serde_json::Map::new();
+                    #krate::MapStruct::try_insert_fields(__value, &mut __nested, "")
+                        .map_err(#serde::ser::Error::custom)?;
+                    __entries.insert(::std::string::ToString::to_string(#key), #krate::__private::Value::Object(__nested));
+                }
+            }
+        } else {
+            quote! {
+                if let ::core::option::Option::Some(__value) = &self.#name {
+                    #krate::__private::insert_field(&mut __entries, #key, __value)
+                        .map_err(#serde::ser::Error::custom)?;
+                }
+            }
+        }
+    });
+
+    quote! {
+        #[doc = #doc]
+        #[allow(dead_code)]
+        #vis struct #patch_ident {
+            #(#fields,)*
+        }
+
+        #[automatically_derived]
+        impl ::core::default::Default for #patch_ident {
+            fn default() -> Self {
+                Self { #(#names: ::core::option::Option::None,)* }
+            }
+        }
+
+        #[automatically_derived]
+        impl #serde::Serialize for #patch_ident {
+            fn serialize<S: #serde::Serializer>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error> {
+                let mut __entries = #krate::__private::serde_json::Map::new();
+                #(#write)*
+                #serde::Serialize::serialize(&__entries, serializer)
+            }
+        }
+
+        #[automatically_derived]
+        impl<'de> #serde::Deserialize<'de> for #patch_ident {
+            fn deserialize<D: #serde::Deserializer<'de>>(deserializer: D) -> ::core::result::Result<Self, D::Error> {
+                let __entries = <#krate::__private::serde_json::Map<::std::string::String, #krate::__private::Value>
+                    as #serde::Deserialize>::deserialize(deserializer)?;
+                if <#ident as #krate::MapStruct>::DENY_UNKNOWN_KEYS {
+                    let keys = <#ident as #krate::MapStruct>::unknown_keys(&__entries);
+                    if !keys.is_empty() {
+                        return ::core::result::Result::Err(#serde::de::Error::custom(
+                            #krate::MapToStructError::UnknownKeys { keys },
+                        ));
+                    }
+                }
+                let __source: &dyn #krate::ValueSource = &__entries;
+                ::core::result::Result::Ok(Self { #(#read,)* })
+            }
+        }
+
+        // Described like the map: an object whose keys are all optional
+        #[automatically_derived]
+        impl #krate::__private::specta::Type for #patch_ident {
+            fn inline(
+                type_map: &mut #krate::__private::specta::TypeCollection,
+                generics: #krate::__private::specta::Generics,
+            ) -> #krate::__private::specta::datatype::DataType {
+                <#ident as #krate::MapStruct>::map_type(type_map, generics)
+            }
+        }
+    }
+}
+
+// Body of `MapStruct::apply_patch`: check every present field against its
+// own rules first, then write them and note which keys changed
+fn apply_patch(container: &Container) -> TokenStream {
+    let krate = &container.krate;
+    let locals: Vec<_> = (0..container.keyed_fields().count()).map(|i| format_ident!("__field{}", i)).collect();
+
+    let check = container.keyed_fields().zip(&locals).map(|(field, local)| {
+        let (name, key) = (&field.ident, &field.key);
+        // Nested values are kept as their object, plain ones as the value
+        let (entry, validate, value) = if field.nested {
+            let entry = quote! {
+                let mut __nested = #krate::__private::serde_json::Map::new();
+                #krate::MapStruct::try_insert_fields(__value, &mut __nested, "")?;
+                let __entry = #krate::__private::patch_value(#key, &__nested)?;
+            };
+            (entry, nested_expr(krate, field), quote! { ::core::option::Option::Some(__nested) })
+        } else {
+            let entry = quote! { let __entry = #krate::__private::patch_value(#key, __value)?; };
+            (entry, field_expr(krate, field), quote! { __entry.into_iter().next().map(|(_, __value)| __value) })
+        };
+        quote! {
+            let #local = match &__patch.#name {
+                ::core::option::Option::Some(__value) => {
+                    #entry
+                    {
+                        let __source: &dyn #krate::ValueSource = &__entry;
+                        #validate?;
+                    }
+                    #value
+                }
+                ::core::option::Option::None => ::core::option::Option::None,
+            };
+        }
+    });
+
+    let write = container.keyed_fields().zip(&locals).map(|(field, local)| {
+        let key = &field.key;
+        let variant = key_variant(field);
+        let replace = if field.nested {
+            quote! { #krate::__private::replace_nested(__map, #key, __value) }
+        } else {
+            quote! { #krate::__private::replace_field(__map, #key, __value) }
+        };
+        quote! {
+            if let ::core::option::Option::Some(__value) = #local {
+                if #replace {
+                    __changed.push(Self::Key::#variant);
+                }
+            }
+        }
+    });
+
+    quote! {
+        #(#check)*
+        let mut __changed = ::std::vec::Vec::new();
+        #(#write)*
+        ::core::result::Result::Ok(__changed)
+    }
+}
+
+// Body of `MapStruct::patch_keys`
+fn patch_keys(container: &Container) -> TokenStream {
+    let present = container.keyed_fields().map(|field| {
+        let name = &field.ident;
+        let variant = key_variant(field);
+        quote! {
+            if __patch.#name.is_some() {
+                __keys.push(Self::Key::#variant);
+            }
+        }
+    });
+    quote! {
+        let mut __keys = ::std::vec::Vec::new();
+        #(#present)*
+        __keys
+    }
+}
+
+// Typed getter and setter for one field, e.g. `fur_length_cm()` and
+// `set_fur_length_cm(..)`
+struct Accessor {
+    getter: TokenStream,
+    setter: TokenStream,
+    get_body: TokenStream,
+    set_body: TokenStream,
+}
+
+// Bodies read through `__source` and write through `__map`, which the
+// caller binds to the entries; `vis` is empty for trait methods
+fn accessors(container: &Container, vis: TokenStream) -> Vec<Accessor> {
+    let krate = &container.krate;
+    container
+        .keyed_fields()
+        .map(|field| {
+            let key = &field.key;
+            let ty = &field.ty;
+            let name = &field.ident;
+            let set_name = format_ident!("set_{}", name.unraw(), span = name.span());
+            let get_doc = format!("Reads `{}` on its own, with the same defaults and checks as `to_typed()`.", key);
+            let set_doc = format!("Writes `{}`, replacing whatever was there.", key);
+
+            let (get_body, set_body) = if field.nested {
+                let set_body = quote! {
+                    // An object under the key would shadow the dotted keys
+                    #krate::ValueMap::remove(__map, #key);
+                    #krate::MapStruct::insert_fields(&value, __map, &::std::format!("{}.", #key));
+                };
+                (nested_expr(krate, field), set_body)
+            } else {
+                (field_expr(krate, field), quote! { #krate::__private::write_field(__map, #key, &value); })
+            };
+
+            Accessor {
+                getter: quote! {
+                    #[doc = #get_doc]
+                    #vis fn #name(&self) -> ::core::result::Result<#ty, #krate::MapToStructError>
+                },
+                setter: quote! {
+                    #[doc = #set_doc]
+                    #vis fn #set_name(&mut self, value: #ty)
+                },
+                get_body,
+                set_body,
+            }
+        })
+        .collect()
+}
+
+// Without a `map` type the accessors go on `StateMap<Struct>`, through a
+// `StructFields` trait since the impl can't be inherent
+fn state_map_accessors(container: &Container) -> TokenStream {
+    let krate = &container.krate;
+    let ident = &container.ident;
+    let vis = &container.vis;
+    let trait_ident = format_ident!("{}Fields", ident.unraw());
+    let doc = format!("Typed access to single fields of a `StateMap<{}>`.", ident.unraw());
+
+    let accessors = accessors(container, TokenStream::new());
+    let signatures = accessors.iter().map(|a| {
+        let (getter, setter) = (&a.getter, &a.setter);
+        quote! { #getter; #setter; }
+    });
+    let methods = accessors.iter().map(|a| {
+        let Accessor { getter, setter, get_body, set_body } = a;
+        quote! {
+            #getter {
+                let __source: &dyn #krate::ValueSource = self;
+                #get_body
+            }
+
+            #setter {
+                let __map: &mut dyn #krate::ValueMap = self;
+                #set_body
+            }
+        }
+    });
+
+    quote! {
+        #[doc = #doc]
+        #[allow(dead_code)]
+        #vis trait #trait_ident {
+            #(#signatures)*
+        }
+
+        #[automatically_derived]
+        impl #trait_ident for #krate::StateMap<#ident> {
+            #(#methods)*
+        }
+    }
+}
+
+// Points errors at `tokens` while still resolving names like `__source` where
+// the expansion declares them, even when the input came through `macro_rules!`
+fn at(tokens: &impl Spanned) -> Span {
+    tokens.span().resolved_at(Span::call_site())
+}
@@ -0,0 +1,119 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{braced, Attribute, Expr, Ident, Token, Type, Visibility};
+
+use crate::attr::{Container, ContainerOptions, FieldOptions};
+
+/// `map_to_struct! { #[opts] Map => Struct { fields } }`, or with
+/// `#[attrs] vis struct Struct { fields }` after the arrow to declare it.
+pub(crate) struct MacroInput {
+    options: Vec<Attribute>,
+    map: Type,
+    declared: Option<DeclaredStruct>,
+    ident: Ident,
+    fields: Punctuated<MacroField, Token![,]>,
+}
+
+struct DeclaredStruct {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+}
+
+struct MacroField {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    ident: Ident,
+    ty: Type,
+    default: Option<(Token![=], Expr)>,
+}
+
+impl Parse for MacroInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let options = input.call(Attribute::parse_outer)?;
+        let map = input.parse()?;
+        input.parse::<Token![=>]>()?;
+
+        let declared = if input.peek(Ident) && input.peek2(syn::token::Brace) {
+            None
+        } else {
+            let attrs = input.call(Attribute::parse_outer)?;
+            let vis = input.parse()?;
+            input.parse::<Token![struct]>()?;
+            Some(DeclaredStruct { attrs, vis })
+        };
+        let ident = input.parse()?;
+
+        let content;
+        braced!(content in input);
+        let fields = content.parse_terminated(MacroField::parse, Token![,])?;
+
+        Ok(Self { options, map, declared, ident, fields })
+    }
+}
+
+impl Parse for MacroField {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let ident = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.parse()?;
+        let default = if input.peek(Token![=]) {
+            Some((input.parse()?, input.parse()?))
+        } else {
+            None
+        };
+        Ok(Self { attrs, vis, ident, ty, default })
+    }
+}
+
+impl MacroInput {
+    /// The struct declaration (if any) with our attributes stripped, and the
+    /// container to expand.
+    pub(crate) fn into_parts(self) -> syn::Result<(TokenStream, Container)> {
+        let mut options = ContainerOptions::default();
+        let rest = options.parse_attrs(self.options, true)?;
+        if let Some(attr) = rest.first() {
+            return Err(syn::Error::new_spanned(attr, "unknown map_to_struct option"));
+        }
+        options.map = Some(self.map);
+
+        let mut declared_fields = Vec::new();
+        let mut fields = Vec::new();
+        for mut field in self.fields {
+            let mut field_options = FieldOptions::default();
+            let attrs = field_options.parse_attrs(field.attrs, true)?;
+            if let Some((eq, expr)) = field.default {
+                field_options.set_default_expr(expr, eq.span)?;
+            }
+
+            let (vis, ident, ty) = (&field.vis, &field.ident, &field.ty);
+            declared_fields.push(quote! { #(#attrs)* #vis #ident: #ty });
+            // Only the patch's copy of the field uses this, and it has to be
+            // settable wherever the patch type can be named
+            if self.declared.is_none() {
+                field.vis = syn::parse_quote!(pub);
+            }
+            fields.push(field_options.into_field(field.vis, field.ident, field.ty, options.rename_all)?);
+        }
+
+        let ident = self.ident;
+        let (declaration, vis) = match self.declared {
+            Some(DeclaredStruct { attrs, vis }) => {
+                let declaration = quote! {
+                    #(#attrs)*
+                    #vis struct #ident {
+                        #(#declared_fields,)*
+                    }
+                };
+                (declaration, vis)
+            }
+            // The struct's own visibility isn't known, so the generated key,
+            // value and patch types can't be any narrower than `pub`
+            None => (TokenStream::new(), syn::parse_quote!(pub)),
+        };
+        Ok((declaration, options.into_container(vis, ident, fields)?))
+    }
+}
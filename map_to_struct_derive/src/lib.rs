@@ -0,0 +1,59 @@
+//! Macros for the `map_to_struct` crate; depend on that crate rather than
+//! on this one directly.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, Data, DeriveInput, Fields};
+
+mod attr;
+mod case;
+mod expand;
+mod input;
+
+use attr::{ContainerOptions, FieldOptions};
+
+/// Implements `MapStruct` for a struct with named fields and, given
+/// `#[map_to_struct(map = MapType)]`, the conversions on that map type.
+#[proc_macro_derive(MapToStruct, attributes(map_to_struct))]
+pub fn derive_map_to_struct(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Backs `map_to_struct::map_to_struct!`, which passes in the crate path.
+#[proc_macro]
+pub fn map_to_struct(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as input::MacroInput);
+    match input.into_parts() {
+        Ok((declaration, container)) => {
+            let impls = expand::expand(&container);
+            quote::quote! { #declaration #impls }.into()
+        }
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+fn derive(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "MapToStruct does not support generic structs"));
+    }
+    let named = match input.data {
+        Data::Struct(data) => match data.fields {
+            Fields::Named(named) => named.named,
+            _ => return Err(syn::Error::new_spanned(&input.ident, "MapToStruct needs named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(&input.ident, "MapToStruct can only be derived for structs")),
+    };
+
+    let mut options = ContainerOptions::default();
+    options.parse_attrs(input.attrs, false)?;
+
+    let mut fields = Vec::new();
+    for field in named {
+        let mut field_options = FieldOptions::default();
+        field_options.parse_attrs(field.attrs, false)?;
+        let ident = field.ident.expect("named field");
+        fields.push(field_options.into_field(field.vis, ident, field.ty, options.rename_all)?);
+    }
+
+    Ok(expand::expand(&options.into_container(input.vis, input.ident, fields)?))
+}
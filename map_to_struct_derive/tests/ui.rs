@@ -0,0 +1,7 @@
+// Compile errors from the derive and `map_to_struct!`, pointing at the
+// offending attribute or field
+#[test]
+fn ui() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,15 @@
+use std::collections::BTreeMap;
+
+use map_to_struct::MapToStruct;
+use serde_json::Value;
+
+#[derive(MapToStruct)]
+struct Theme {
+    name: String,
+    #[map_to_struct(rest)]
+    extra: BTreeMap<String, Value>,
+    #[map_to_struct(rest)]
+    more: BTreeMap<String, Value>,
+}
+
+fn main() {}
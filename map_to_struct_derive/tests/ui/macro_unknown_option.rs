@@ -0,0 +1,15 @@
+use std::collections::HashMap;
+
+use map_to_struct::map_to_struct;
+use serde_json::Value;
+
+struct VisitMap(HashMap<String, Value>);
+
+map_to_struct! {
+    #[rename = "camelCase"]
+    VisitMap => struct Visit {
+        name: String,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use map_to_struct::{MapToStruct, ValueMap};
+
+fn migrate_visit(_from: u32, _map: &mut dyn ValueMap) {}
+
+#[derive(MapToStruct)]
+#[map_to_struct(migrate = migrate_visit)]
+struct Visit {
+    name: String,
+}
+
+fn main() {}
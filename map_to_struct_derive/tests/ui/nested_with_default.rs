@@ -0,0 +1,14 @@
+use map_to_struct::MapToStruct;
+
+#[derive(Default, MapToStruct)]
+struct Audio {
+    volume: u8,
+}
+
+#[derive(MapToStruct)]
+struct Settings {
+    #[map_to_struct(nested, default)]
+    audio: Audio,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use map_to_struct::MapToStruct;
+
+#[derive(MapToStruct)]
+enum Coat {
+    Short,
+    Long,
+}
+
+#[derive(MapToStruct)]
+struct Point(i32, i32);
+
+fn main() {}
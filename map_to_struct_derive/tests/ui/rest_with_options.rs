@@ -0,0 +1,13 @@
+use std::collections::BTreeMap;
+
+use map_to_struct::MapToStruct;
+use serde_json::Value;
+
+#[derive(MapToStruct)]
+struct Theme {
+    name: String,
+    #[map_to_struct(rest, key = "extra")]
+    extra: BTreeMap<String, Value>,
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use std::collections::BTreeMap;
+
+use map_to_struct::MapToStruct;
+use serde_json::Value;
+
+#[derive(MapToStruct)]
+#[map_to_struct(strict)]
+struct Theme {
+    name: String,
+    #[map_to_struct(rest)]
+    extra: BTreeMap<String, Value>,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use map_to_struct::MapToStruct;
+
+#[derive(MapToStruct)]
+#[map_to_struct(rename = "camelCase")]
+struct Visit {
+    name: String,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use map_to_struct::MapToStruct;
+
+#[derive(MapToStruct)]
+struct Visit {
+    #[map_to_struct(optional)]
+    name: String,
+}
+
+fn main() {}
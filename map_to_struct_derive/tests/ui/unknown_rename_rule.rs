@@ -0,0 +1,9 @@
+use map_to_struct::MapToStruct;
+
+#[derive(MapToStruct)]
+#[map_to_struct(rename_all = "camel")]
+struct Visit {
+    fur_length_cm: i32,
+}
+
+fn main() {}
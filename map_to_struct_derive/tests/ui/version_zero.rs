@@ -0,0 +1,9 @@
+use map_to_struct::MapToStruct;
+
+#[derive(MapToStruct)]
+#[map_to_struct(version = 0)]
+struct Visit {
+    name: String,
+}
+
+fn main() {}
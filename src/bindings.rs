@@ -0,0 +1,31 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use specta::datatype::DataType;
+use specta::{Generics, Type, TypeCollection};
+
+// Anonymous object type whose fields are all optional, i.e. `Partial<..>`
+pub fn partial_object(name: &'static str, fields: Vec<(Cow<'static, str>, DataType)>) -> DataType {
+    use specta::internal::construct;
+
+    let fields = fields
+        .into_iter()
+        .map(|(key, ty)| (key, construct::field(true, false, None, Cow::Borrowed(""), Some(ty))))
+        .collect();
+    construct::r#struct(Cow::Borrowed(name), None, vec![], construct::struct_named(fields, None))
+        .to_anonymous()
+}
+
+// TypeScript's `unknown`, for raw `Value`s like those of a `Record<string, unknown>`
+pub(crate) struct UnknownValue;
+
+impl Type for UnknownValue {
+    fn inline(_type_map: &mut TypeCollection, _generics: Generics) -> DataType {
+        DataType::Unknown
+    }
+}
+
+// `Record<string, unknown>`, for `specta = "record"`
+pub fn record_type(type_map: &mut TypeCollection, generics: Generics) -> DataType {
+    <HashMap<String, UnknownValue> as Type>::inline(type_map, generics)
+}
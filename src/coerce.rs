@@ -0,0 +1,97 @@
+use serde::Deserialize;
+use serde_json::{Number, Value};
+
+use crate::field::{invalid, lookup, missing_field};
+use crate::{MapToStructError, ValueMap, ValueSource};
+
+// Like `extract_field_or`, but a value that doesn't deserialize as `T` gets
+// a second chance as each of its loosely typed readings, for `coerce` fields
+pub fn extract_coerced_or<T>(
+    source: &dyn ValueSource,
+    key: &str,
+    aliases: &[&str],
+    missing: impl FnOnce() -> Option<T>,
+) -> Result<T, MapToStructError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    match lookup(source, key, aliases) {
+        Some(v) => deserialize_coerced(key, v),
+        None => missing_field(key, missing),
+    }
+}
+
+// `extract_coerced_or`, removing the value from `map`. It's still read by
+// reference, since a failed attempt mustn't consume it.
+pub fn take_coerced_or<T>(
+    map: &mut dyn ValueMap,
+    key: &str,
+    aliases: &[&str],
+    missing: impl FnOnce() -> Option<T>,
+) -> Result<T, MapToStructError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let value = map.remove(key).or_else(|| aliases.iter().find_map(|alias| map.remove(alias)));
+    match value {
+        Some(v) => deserialize_coerced(key, &v),
+        None => missing_field(key, missing),
+    }
+}
+
+// Whether the stored value only converts to `T` by coercion, for the report
+pub fn is_coerced<T>(source: &dyn ValueSource, key: &str, aliases: &[&str]) -> bool
+where
+    T: for<'de> Deserialize<'de>,
+{
+    lookup(source, key, aliases).is_some_and(|v| T::deserialize(v).is_err())
+}
+
+// The value as stored if it fits, else the first reading that does. When
+// none do the error is about the value as stored.
+fn deserialize_coerced<T>(key: &str, value: &Value) -> Result<T, MapToStructError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    T::deserialize(value).or_else(|source| {
+        readings(value).iter().find_map(|v| T::deserialize(v).ok()).ok_or_else(|| invalid::<T>(key, source))
+    })
+}
+
+// `"7"` -> 7, `"2.5"` -> 2.5, `"true"` -> true, `3.0` -> 3 and `0`/`1` ->
+// false/true, in the order they're tried
+fn readings(value: &Value) -> Vec<Value> {
+    match value {
+        Value::String(s) => match s.trim() {
+            "true" => vec![Value::Bool(true)],
+            "false" => vec![Value::Bool(false)],
+            s => match s.parse::<Number>() {
+                Ok(n) => {
+                    let mut readings = number_readings(&n);
+                    readings.insert(0, Value::Number(n));
+                    readings
+                }
+                Err(_) => Vec::new(),
+            },
+        },
+        Value::Number(n) => number_readings(n),
+        _ => Vec::new(),
+    }
+}
+
+fn number_readings(n: &Number) -> Vec<Value> {
+    let integer = match n.as_f64() {
+        Some(f) if n.is_f64() && f.fract() == 0.0 && f >= i64::MIN as f64 && f < i64::MAX as f64 => Some(f as i64),
+        _ => n.as_i64(),
+    };
+    let mut readings = Vec::new();
+    if n.is_f64() {
+        readings.extend(integer.map(Value::from));
+    }
+    match integer {
+        Some(0) => readings.push(Value::Bool(false)),
+        Some(1) => readings.push(Value::Bool(true)),
+        _ => {}
+    }
+    readings
+}
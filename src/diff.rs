@@ -0,0 +1,50 @@
+use serde::Serialize;
+use serde_json::Value;
+use specta::Type;
+
+use crate::bindings::UnknownValue;
+use crate::field::{lookup, prefixed_key};
+use crate::source::nested_source;
+use crate::{MapStruct, ValueSource};
+
+/// A tracked key whose value differs between two maps or structs. An absent
+/// key counts as `null`; nested fields are reported by their dotted key.
+#[derive(Debug, Clone, PartialEq, Serialize, Type)]
+pub struct FieldChange {
+    pub key: String,
+    #[specta(type = UnknownValue)]
+    pub old: Value,
+    #[specta(type = UnknownValue)]
+    pub new: Value,
+}
+
+// A plain field, read from its key or an alias on either side
+pub fn diff_field(
+    old: &dyn ValueSource,
+    new: &dyn ValueSource,
+    prefix: &str,
+    key: &str,
+    aliases: &[&str],
+    changes: &mut Vec<FieldChange>,
+) {
+    let old = lookup(old, key, aliases).unwrap_or(&Value::Null);
+    let new = lookup(new, key, aliases).unwrap_or(&Value::Null);
+    if old != new {
+        changes.push(FieldChange { key: prefixed_key(prefix, key).into_owned(), old: old.clone(), new: new.clone() });
+    }
+}
+
+// A nested field, compared field by field whether it's held as an object or
+// as dotted keys. A value that's neither is compared whole.
+pub fn diff_nested<T: MapStruct>(
+    old: &dyn ValueSource,
+    new: &dyn ValueSource,
+    prefix: &str,
+    key: &str,
+    changes: &mut Vec<FieldChange>,
+) {
+    match (nested_source(old, key), nested_source(new, key)) {
+        (Ok(old), Ok(new)) => T::diff_sources(&old, &new, &format!("{}{}.", prefix, key), changes),
+        _ => diff_field(old, new, prefix, key, &[], changes),
+    }
+}
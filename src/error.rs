@@ -0,0 +1,182 @@
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+use specta::Type;
+
+/// Why a map couldn't be converted, tagged by `kind` for the TypeScript side.
+#[derive(Debug, Serialize, Type)]
+#[serde(tag = "kind")]
+pub enum MapToStructError {
+    /// The key is not present in the map
+    Missing { key: String },
+    /// The key is present but its value doesn't deserialize. `expected_type`
+    /// is the JSON kind the field reads: `"boolean"`, `"integer"`,
+    /// `"number"`, `"string"`, `"array"`, `"object"`, `"null"`, `"enum"` or
+    /// `"any"`
+    Invalid {
+        key: String,
+        expected_type: &'static str,
+        #[serde(serialize_with = "serialize_display")]
+        #[specta(type = String)]
+        source: serde_json::Error,
+    },
+    /// The value deserialized but was rejected by a validation attribute
+    Validation { key: String, message: String },
+    /// The map has keys no field reads, under `deny_unknown_keys`
+    UnknownKeys { keys: Vec<String> },
+    /// The map's `__version` is newer than the struct's `version = N`
+    UnsupportedVersion { version: u32, supported: u32 },
+    /// The value to write doesn't serialize as JSON, e.g. a map with
+    /// non-string keys
+    Unserializable {
+        key: String,
+        #[serde(serialize_with = "serialize_display")]
+        #[specta(type = String)]
+        source: serde_json::Error,
+    },
+}
+
+impl MapToStructError {
+    /// The offending key; the first one for `UnknownKeys`.
+    pub fn key(&self) -> &str {
+        match self {
+            Self::Missing { key }
+            | Self::Invalid { key, .. }
+            | Self::Validation { key, .. }
+            | Self::Unserializable { key, .. } => key,
+            Self::UnknownKeys { keys } => keys.first().map_or("", String::as_str),
+            Self::UnsupportedVersion { .. } => crate::VERSION_KEY,
+        }
+    }
+
+    // Reports a nested field's error under its full path, e.g. `audio.volume`
+    pub(crate) fn prefixed(mut self, prefix: &str) -> Self {
+        match &mut self {
+            Self::Missing { key }
+            | Self::Invalid { key, .. }
+            | Self::Validation { key, .. }
+            | Self::Unserializable { key, .. } => *key = format!("{}.{}", prefix, key),
+            Self::UnknownKeys { keys } => {
+                for key in keys {
+                    *key = format!("{}.{}", prefix, key);
+                }
+            }
+            Self::UnsupportedVersion { .. } => {}
+        }
+        self
+    }
+}
+
+impl fmt::Display for MapToStructError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing { key } => write!(f, "Missing {}", key),
+            Self::Invalid { key, source, .. } => write!(f, "Invalid {}: {}", key, source),
+            Self::Validation { key, message } => write!(f, "Invalid {}: {}", key, message),
+            Self::UnknownKeys { keys } => write!(f, "Unknown keys {}", keys.join(", ")),
+            Self::UnsupportedVersion { version, supported } => {
+                write!(f, "Unsupported version {}, expected {} or older", version, supported)
+            }
+            Self::Unserializable { key, source } => write!(f, "Cannot serialize {}: {}", key, source),
+        }
+    }
+}
+
+impl std::error::Error for MapToStructError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Missing { .. }
+            | Self::Validation { .. }
+            | Self::UnknownKeys { .. }
+            | Self::UnsupportedVersion { .. } => None,
+            Self::Invalid { source, .. } | Self::Unserializable { source, .. } => Some(source),
+        }
+    }
+}
+
+fn serialize_display<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: fmt::Display,
+    S: Serializer,
+{
+    serializer.collect_str(value)
+}
+
+/// A string that isn't one of a struct's keys, from a generated `...Key`
+/// enum's `FromStr`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownKey {
+    pub key: String,
+}
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown key {}", self.key)
+    }
+}
+
+impl std::error::Error for UnknownKey {}
+
+/// Outcome of `to_typed_report`: every field is attempted, so `errors` lists
+/// all broken keys at once. `value` is only set when nothing failed.
+/// `coerced` lists the converted keys of `coerce` fields whose value had the
+/// wrong JSON type, e.g. `"7"` for a number.
+#[derive(Debug, Serialize, Type)]
+pub struct MapToStructReport<T> {
+    pub value: Option<T>,
+    pub converted: Vec<String>,
+    pub coerced: Vec<String>,
+    pub errors: Vec<MapToStructError>,
+}
+
+impl<T> Default for MapToStructReport<T> {
+    fn default() -> Self {
+        Self { value: None, converted: Vec::new(), coerced: Vec::new(), errors: Vec::new() }
+    }
+}
+
+impl<T> MapToStructReport<T> {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn into_result(self) -> Result<T, Vec<MapToStructError>> {
+        match self.value {
+            Some(value) if self.errors.is_empty() => Ok(value),
+            _ => Err(self.errors),
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn record<F>(&mut self, key: &str, result: Result<F, MapToStructError>) -> Option<F> {
+        match result {
+            Ok(value) => {
+                self.converted.push(key.to_string());
+                Some(value)
+            }
+            Err(e) => {
+                self.errors.push(e);
+                None
+            }
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn record_nested<F>(
+        &mut self,
+        key: &str,
+        result: Result<MapToStructReport<F>, MapToStructError>,
+    ) -> Option<F> {
+        let nested = match result {
+            Ok(nested) => nested,
+            Err(e) => {
+                self.errors.push(e);
+                return None;
+            }
+        };
+        self.converted.extend(nested.converted.into_iter().map(|k| format!("{}.{}", key, k)));
+        self.coerced.extend(nested.coerced.into_iter().map(|k| format!("{}.{}", key, k)));
+        self.errors.extend(nested.errors.into_iter().map(|e| e.prefixed(key)));
+        nested.value
+    }
+}
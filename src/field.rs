@@ -0,0 +1,190 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::kind::json_kind;
+use crate::patch::{is_absent, MissingValue};
+use crate::schema::is_required;
+use crate::{MapToStructError, ValueMap, ValueSource};
+
+// Looks up `key`, then each alias in turn
+pub(crate) fn lookup<'a>(source: &'a dyn ValueSource, key: &str, aliases: &[&str]) -> Option<&'a Value> {
+    source.get_value(key).or_else(|| aliases.iter().find_map(|alias| source.get_value(alias)))
+}
+
+/// Reads `key` from `source` as a `T`. An absent key is `Missing` unless `T`
+/// is an `Option` or [`Patch`](crate::Patch); a value of the wrong type is
+/// `Invalid`.
+pub fn extract_field<T>(source: &dyn ValueSource, key: &str) -> Result<T, MapToStructError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    extract_field_or(source, key, &[], || None)
+}
+
+// An absent key falls back to `missing()` (the field's default, if any),
+// then to whatever `T` makes of a missing value: `None` for `Option<T>`,
+// `Patch::Absent` for `Patch<T>`. Type mismatches are always `Invalid`.
+pub fn extract_field_or<T>(
+    source: &dyn ValueSource,
+    key: &str,
+    aliases: &[&str],
+    missing: impl FnOnce() -> Option<T>,
+) -> Result<T, MapToStructError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    // `&Value` is a `Deserializer` itself, so nothing is cloned up front
+    match lookup(source, key, aliases) {
+        Some(v) => T::deserialize(v).map_err(|source| invalid::<T>(key, source)),
+        None => missing_field(key, missing),
+    }
+}
+
+// Like `extract_field_or`, but moves the value out of `map` so strings and
+// arrays are reused rather than copied
+pub fn take_field_or<T>(
+    map: &mut dyn ValueMap,
+    key: &str,
+    aliases: &[&str],
+    missing: impl FnOnce() -> Option<T>,
+) -> Result<T, MapToStructError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let value = map.remove(key).or_else(|| aliases.iter().find_map(|alias| map.remove(alias)));
+    match value {
+        Some(v) => serde_json::from_value(v).map_err(|source| invalid::<T>(key, source)),
+        None => missing_field(key, missing),
+    }
+}
+
+pub(crate) fn invalid<T>(key: &str, source: serde_json::Error) -> MapToStructError
+where
+    T: for<'de> Deserialize<'de>,
+{
+    MapToStructError::Invalid { key: key.to_string(), expected_type: json_kind::<T>(), source }
+}
+
+pub(crate) fn missing_field<T>(key: &str, missing: impl FnOnce() -> Option<T>) -> Result<T, MapToStructError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    missing()
+        .or_else(|| T::deserialize(MissingValue).ok())
+        .ok_or_else(|| MapToStructError::Missing { key: key.to_string() })
+}
+
+// A present `null` for a field that accepts a missing value (`Option`,
+// `Patch`), which a patch writes to clear the field. Anything else is `None`
+// and read as usual, where `null` leaves the field alone.
+pub fn patch_null<T>(source: &dyn ValueSource, key: &str, aliases: &[&str]) -> Option<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    match lookup(source, key, aliases) {
+        Some(Value::Null) if !is_required::<T>() => T::deserialize(&Value::Null).ok(),
+        _ => None,
+    }
+}
+
+/// Serializes `value` into `map` under `key`, replacing any previous value.
+/// An absent [`Patch`](crate::Patch) removes the key instead, so it reads
+/// back as absent rather than `null`. Fails with `Unserializable` for values
+/// serde_json can't represent, e.g. maps with non-string keys.
+pub fn insert_field<M, T>(map: &mut M, key: &str, value: &T) -> Result<(), MapToStructError>
+where
+    M: ValueMap + ?Sized,
+    T: Serialize,
+{
+    if is_absent(value) {
+        map.remove(key);
+    } else {
+        map.insert(key.to_string(), to_value(key, value)?);
+    }
+    Ok(())
+}
+
+// `insert_field` for writes that can't fail (`from_typed()`, setters), where
+// a value serde_json can't represent is a bug in the field's type
+pub fn write_field<T: Serialize>(map: &mut dyn ValueMap, key: &str, value: &T) {
+    if let Err(err) = insert_field(map, key, value) {
+        panic!("{}", err);
+    }
+}
+
+fn to_value<T: Serialize>(key: &str, value: &T) -> Result<Value, MapToStructError> {
+    serde_json::to_value(value).map_err(|source| MapToStructError::Unserializable { key: key.to_string(), source })
+}
+
+// A patch field as the only entry of a map, so the field's own checks can
+// run on it before anything is written
+pub fn patch_value<T: Serialize>(key: &str, value: &T) -> Result<serde_json::Map<String, Value>, MapToStructError> {
+    let mut entry = serde_json::Map::new();
+    entry.insert(key.to_string(), to_value(key, value)?);
+    Ok(entry)
+}
+
+// Writes `value` unless it's already there; true if the map changed
+pub fn replace_field(map: &mut dyn ValueMap, key: &str, value: Value) -> bool {
+    if map.get(key) == Some(&value) {
+        return false;
+    }
+    map.insert(key.to_string(), value);
+    true
+}
+
+// Replaces a nested struct's entries, leaving an identical object alone but
+// otherwise writing dotted keys like `from_typed()` does
+pub fn replace_nested(map: &mut dyn ValueMap, key: &str, object: serde_json::Map<String, Value>) -> bool {
+    if let Some(Value::Object(current)) = map.get(key) {
+        if *current == object {
+            return false;
+        }
+    }
+    let mut changed = map.remove(key).is_some();
+    for (field, value) in object {
+        changed |= replace_field(map, &format!("{}.{}", key, field), value);
+    }
+    changed
+}
+
+// Every entry `known` rejects, for a `rest` field
+pub fn extract_rest<T>(source: &dyn ValueSource, known: impl Fn(&str) -> bool) -> T
+where
+    T: FromIterator<(String, Value)>,
+{
+    source
+        .source_keys()
+        .filter(|key| !known(key))
+        .filter_map(|key| Some((key.to_string(), source.get_value(key)?.clone())))
+        .collect()
+}
+
+// Moves every entry `known` rejects out of `map`, for a `rest` field
+pub fn take_rest<T>(map: &mut dyn ValueMap, known: impl Fn(&str) -> bool) -> T
+where
+    T: FromIterator<(String, Value)>,
+{
+    let keys: Vec<String> = map.keys().filter(|key| !known(key)).map(str::to_string).collect();
+    keys.into_iter().filter_map(|key| Some((key.clone(), map.remove(&key)?))).collect()
+}
+
+// Spreads a `rest` field back into the map
+pub fn insert_rest<'a, T>(map: &mut dyn ValueMap, prefix: &str, rest: &'a T)
+where
+    &'a T: IntoIterator<Item = (&'a String, &'a Value)>,
+{
+    for (key, value) in rest {
+        map.insert(prefixed_key(prefix, key).into_owned(), value.clone());
+    }
+}
+
+pub fn prefixed_key<'a>(prefix: &str, key: &'a str) -> Cow<'a, str> {
+    if prefix.is_empty() {
+        Cow::Borrowed(key)
+    } else {
+        Cow::Owned(format!("{}{}", prefix, key))
+    }
+}
@@ -0,0 +1,87 @@
+use std::cell::Cell;
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+
+// The JSON kind `T` deserializes from, for `MapToStructError::Invalid`:
+// "boolean", "integer", "number", "string", "array", "object", "null",
+// "enum" or, for types that take anything, "any". `Option<T>`, `Patch<T>`
+// and newtypes report the kind of what they wrap.
+pub(crate) fn json_kind<T>() -> &'static str
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let kind = Cell::new("any");
+    let _ = T::deserialize(KindProbe { kind: &kind });
+    kind.get()
+}
+
+// Notes which `deserialize_*` method the type asks for, then gives up
+struct KindProbe<'a> {
+    kind: &'a Cell<&'static str>,
+}
+
+impl KindProbe<'_> {
+    fn found<T>(self, kind: &'static str) -> Result<T, de::value::Error> {
+        self.kind.set(kind);
+        Err(de::Error::custom("kind probe"))
+    }
+}
+
+macro_rules! probe {
+    ($($method:ident($($arg:ident: $ty:ty),*) => $kind:literal,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, $($arg: $ty,)* _visitor: V) -> Result<V::Value, Self::Error> {
+                $(let _ = $arg;)*
+                self.found($kind)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for KindProbe<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    probe! {
+        deserialize_any() => "any",
+        deserialize_ignored_any() => "any",
+        deserialize_bool() => "boolean",
+        deserialize_i8() => "integer",
+        deserialize_i16() => "integer",
+        deserialize_i32() => "integer",
+        deserialize_i64() => "integer",
+        deserialize_i128() => "integer",
+        deserialize_u8() => "integer",
+        deserialize_u16() => "integer",
+        deserialize_u32() => "integer",
+        deserialize_u64() => "integer",
+        deserialize_u128() => "integer",
+        deserialize_f32() => "number",
+        deserialize_f64() => "number",
+        deserialize_char() => "string",
+        deserialize_str() => "string",
+        deserialize_string() => "string",
+        deserialize_identifier() => "string",
+        deserialize_bytes() => "array",
+        deserialize_byte_buf() => "array",
+        deserialize_seq() => "array",
+        deserialize_tuple(len: usize) => "array",
+        deserialize_tuple_struct(name: &'static str, len: usize) => "array",
+        deserialize_map() => "object",
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]) => "object",
+        deserialize_unit() => "null",
+        deserialize_unit_struct(name: &'static str) => "null",
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]) => "enum",
+    }
+}
@@ -0,0 +1,306 @@
+//! Converts between a flexible `HashMap<String, serde_json::Value>` state map
+//! (or any other [`ValueMap`]) and a strongly typed struct that tools like specta can describe.
+//!
+//! Put `#[derive(MapToStruct)]` on the struct and name the map wrapper:
+//!
+//! ```ignore
+//! #[derive(Debug, Clone, Serialize, Deserialize, specta::Type, MapToStruct)]
+//! #[map_to_struct(map = GroomingStateMap)]
+//! pub struct GroomingRecord {
+//!     pub fur_length_cm: i32,
+//!     #[map_to_struct(range(0..=10), default = 0)]
+//!     pub shedding_score: u8,
+//! }
+//! ```
+//!
+//! or store the keys in a [`StateMap<GroomingRecord>`](StateMap) and skip the
+//! wrapper altogether. The [`map_to_struct!`] macro does the same as the
+//! derive and can also declare the struct. See the README for every field and
+//! container attribute.
+
+use std::path::Path;
+
+use serde_json::Value;
+use specta::datatype::DataType;
+use specta::{Generics, TypeCollection};
+use specta_typescript::{ExportError, Typescript};
+
+mod bindings;
+mod coerce;
+mod diff;
+mod error;
+mod field;
+mod kind;
+mod observable;
+mod patch;
+mod schema;
+mod source;
+mod state_map;
+mod typescript;
+mod validate;
+mod value_map;
+mod version;
+
+pub use diff::FieldChange;
+pub use error::{MapToStructError, MapToStructReport, UnknownKey};
+pub use field::{extract_field, insert_field};
+pub use map_to_struct_derive::MapToStruct;
+pub use observable::{KeyChange, ObservableMap, SubscriptionId};
+pub use patch::Patch;
+pub use schema::{MapSchema, SchemaBuilder};
+pub use source::ValueSource;
+pub use specta_typescript;
+pub use state_map::StateMap;
+pub use validate::IsEmpty;
+pub use value_map::ValueMap;
+pub use version::VERSION_KEY;
+
+/// Implemented by `#[derive(MapToStruct)]` / [`map_to_struct!`] for each
+/// struct, so generated structs can be nested in one another.
+pub trait MapStruct: Sized {
+    /// The generated `...Key` enum, one variant per field.
+    type Key;
+
+    /// The generated `...Patch` struct, every field an `Option`.
+    type Patch;
+
+    /// The generated `...Value` enum, one variant per field holding its
+    /// typed value.
+    type Value;
+
+    /// Set by `deny_unknown_keys`: [`from_map`](Self::from_map) then rejects
+    /// keys that no field reads.
+    const DENY_UNKNOWN_KEYS: bool = false;
+
+    /// Set by `version = N`: written under [`VERSION_KEY`], and maps with an
+    /// older one are [`upgrade`](Self::upgrade)d before converting.
+    const VERSION: Option<u32> = None;
+
+    /// Brings `map` from version `from` to `from + 1`; the function named by
+    /// `migrate = ..`.
+    fn migrate(from: u32, map: &mut dyn ValueMap) {
+        let _ = (from, map);
+    }
+
+    /// Runs [`migrate`](Self::migrate) for every version between the one
+    /// stored in `map` (0 if there is none) and [`VERSION`](Self::VERSION),
+    /// then stores the new version. Returns whether anything ran.
+    fn upgrade(map: &mut dyn ValueMap) -> Result<bool, MapToStructError> {
+        version::upgrade::<Self>(map)
+    }
+
+    /// Converts every field, stopping at the first error.
+    fn extract(source: &dyn ValueSource) -> Result<Self, MapToStructError>;
+
+    /// Converts every field, collecting all errors.
+    fn extract_report(source: &dyn ValueSource) -> MapToStructReport<Self>;
+
+    /// Whether some field reads `key`: its own key or an alias, or a key
+    /// inside a nested field.
+    fn is_known_key(key: &str) -> bool;
+
+    /// The field that reads `key`: its own key or an alias, or a key inside
+    /// a nested field.
+    fn key_of(key: &str) -> Option<Self::Key>;
+
+    /// Reads one field, with the same defaults and checks as
+    /// [`extract`](Self::extract).
+    fn read_value(source: &dyn ValueSource, key: Self::Key) -> Result<Self::Value, MapToStructError>;
+
+    /// Writes one field, replacing whatever was there.
+    fn write_value(map: &mut dyn ValueMap, value: &Self::Value);
+
+    /// The field `value` belongs to.
+    fn value_key(value: &Self::Value) -> Self::Key;
+
+    /// Converts a whole map like [`extract`](Self::extract), but first
+    /// rejects unknown keys under `deny_unknown_keys`. An outdated map is
+    /// upgraded on a copy; `map` itself is left as it is.
+    fn from_map<M: ValueMap>(map: &M) -> Result<Self, MapToStructError> {
+        if version::is_outdated::<Self, _>(map)? {
+            return Self::from_map_owned(version::copy_map(map));
+        }
+        if Self::DENY_UNKNOWN_KEYS {
+            let keys = Self::unknown_keys(map);
+            if !keys.is_empty() {
+                return Err(MapToStructError::UnknownKeys { keys });
+            }
+        }
+        Self::extract(map)
+    }
+
+    /// Like [`extract`](Self::extract), but moves each value out of `map`
+    /// instead of deserializing it in place. Converted entries are removed.
+    fn take(map: &mut dyn ValueMap) -> Result<Self, MapToStructError>;
+
+    /// [`from_map`](Self::from_map) for a map that's no longer needed, so
+    /// its values can be moved rather than copied.
+    fn from_map_owned<M: ValueMap>(mut map: M) -> Result<Self, MapToStructError> {
+        Self::upgrade(&mut map)?;
+        if Self::DENY_UNKNOWN_KEYS {
+            let keys = Self::unknown_keys(&map);
+            if !keys.is_empty() {
+                return Err(MapToStructError::UnknownKeys { keys });
+            }
+        }
+        Self::take(&mut map)
+    }
+
+    /// [`from_map`](Self::from_map), collecting all errors.
+    fn from_map_report<M: ValueMap>(map: &M) -> MapToStructReport<Self> {
+        match version::is_outdated::<Self, _>(map) {
+            Ok(false) => {}
+            Ok(true) => {
+                let mut upgraded = version::copy_map(map);
+                return match Self::upgrade(&mut upgraded) {
+                    Ok(_) => Self::from_map_report(&upgraded),
+                    Err(e) => MapToStructReport { errors: vec![e], ..Default::default() },
+                };
+            }
+            Err(e) => return MapToStructReport { errors: vec![e], ..Default::default() },
+        }
+        let mut report = Self::extract_report(map);
+        if Self::DENY_UNKNOWN_KEYS {
+            let keys = Self::unknown_keys(map);
+            if !keys.is_empty() {
+                report.value = None;
+                report.errors.push(MapToStructError::UnknownKeys { keys });
+            }
+        }
+        report
+    }
+
+    /// Appends the keys in `source` that no field reads, prefixed by
+    /// `prefix`. A nested field's keys are checked against the nested
+    /// struct, whether they're held in an object or as dotted keys.
+    fn unknown_source_keys(source: &dyn ValueSource, prefix: &str, unknown: &mut Vec<String>);
+
+    /// Keys in `map` that no field reads, sorted; keys inside a nested
+    /// object are listed by their dotted path, e.g. `audio.bass`.
+    fn unknown_keys<M: ValueMap + ?Sized>(map: &M) -> Vec<String> {
+        let mut keys = Vec::new();
+        Self::unknown_source_keys(&source::MapSource(map), "", &mut keys);
+        keys.sort();
+        keys
+    }
+
+    /// Appends a change for every tracked key whose value differs between
+    /// `old` and `new`, with keys prefixed by `prefix`.
+    fn diff_sources(old: &dyn ValueSource, new: &dyn ValueSource, prefix: &str, changes: &mut Vec<FieldChange>);
+
+    /// The tracked keys whose values differ between two maps, in field
+    /// order. Keys no field reads are ignored.
+    fn diff_maps<M: ValueMap>(old: &M, new: &M) -> Vec<FieldChange> {
+        let mut changes = Vec::new();
+        Self::diff_sources(old, new, "", &mut changes);
+        changes
+    }
+
+    /// [`diff_maps`](Self::diff_maps) of the two structs as `from_typed()`
+    /// would write them.
+    fn diff(&self, other: &Self) -> Vec<FieldChange> {
+        let (mut old, mut new) = (serde_json::Map::new(), serde_json::Map::new());
+        self.insert_map(&mut old);
+        other.insert_map(&mut new);
+        Self::diff_maps(&old, &new)
+    }
+
+    /// Writes the struct as the outermost one, like `from_typed()`: every
+    /// field, then `__version` for a versioned struct.
+    fn insert_map(&self, map: &mut dyn ValueMap) {
+        self.insert_fields(map, "");
+        if let Some(version) = Self::VERSION {
+            field::write_field(map, VERSION_KEY, &version);
+        }
+    }
+
+    /// Writes every field into `map`, with keys prefixed by `prefix`. Never
+    /// writes a version, since a nested struct's isn't checked. Fails with
+    /// `Unserializable` if a field doesn't serialize as JSON.
+    fn try_insert_fields(&self, map: &mut dyn ValueMap, prefix: &str) -> Result<(), MapToStructError>;
+
+    /// Like [`try_insert_fields`](Self::try_insert_fields), for writes that
+    /// can't fail such as `from_typed()`: panics if a field doesn't serialize.
+    fn insert_fields(&self, map: &mut dyn ValueMap, prefix: &str) {
+        if let Err(err) = self.try_insert_fields(map, prefix) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Validates every field present in `patch`, then writes them all into
+    /// `map`. Returns the keys whose value changed; nothing is written if
+    /// any field is rejected.
+    fn apply_patch(map: &mut dyn ValueMap, patch: Self::Patch) -> Result<Vec<Self::Key>, MapToStructError>;
+
+    /// The keys of the fields present in `patch`, in field order: the most
+    /// [`apply_patch`](Self::apply_patch) can change.
+    fn patch_keys(patch: &Self::Patch) -> Vec<Self::Key>;
+
+    /// Describes the keys for [`json_schema`](Self::json_schema), collecting
+    /// the named types the fields use in `builder`.
+    fn map_schema(builder: &mut SchemaBuilder) -> MapSchema;
+
+    /// A JSON Schema (draft 2020-12) for a map holding this struct's keys.
+    fn json_schema() -> Value {
+        let mut builder = SchemaBuilder::default();
+        let schema = Self::map_schema(&mut builder);
+        builder.finish(schema)
+    }
+
+    /// A standalone TypeScript module for a map holding this struct's keys:
+    /// the record with one property per `…Key` variant (a nested struct is
+    /// one object, as in the `…Value` enum), the same key union, a patch type
+    /// and typed get/set wrappers around an `invoke` function.
+    fn typescript(config: &Typescript) -> Result<String, ExportError>;
+
+    /// Writes [`typescript`](Self::typescript) to `path`, e.g. from a test.
+    fn export_typescript(config: &Typescript, path: impl AsRef<Path>) -> Result<(), ExportError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, Self::typescript(config)?)?;
+        config.format(path)
+    }
+
+    /// The specta type of a map holding this struct's keys; `Record<string,
+    /// unknown>` unless the derive knows better.
+    fn map_type(type_map: &mut TypeCollection, generics: Generics) -> DataType {
+        bindings::record_type(type_map, generics)
+    }
+}
+
+/// `map_to_struct! { Map => Struct { field: Type, .. } }` for an existing
+/// struct, or `Map => #[derive(..)] pub struct Struct { .. }` to declare it.
+/// Takes the same attributes as `#[derive(MapToStruct)]`, plus their short
+/// bare forms (`#[default]`, `#[range(..)]`, ...).
+#[macro_export]
+macro_rules! map_to_struct {
+    ($($input:tt)*) => {
+        $crate::__private::map_to_struct! {
+            #[map_to_struct(crate = $crate)]
+            $($input)*
+        }
+    };
+}
+
+// Used by the generated code only
+#[doc(hidden)]
+pub mod __private {
+    pub use map_to_struct_derive::map_to_struct;
+    pub use serde;
+    pub use serde_json::{self, Value};
+    pub use specta;
+
+    pub use crate::bindings::{partial_object, record_type};
+    pub use crate::coerce::{extract_coerced_or, is_coerced, take_coerced_or};
+    pub use crate::diff::{diff_field, diff_nested};
+    pub use crate::field::{
+        extract_field_or, extract_rest, insert_field, insert_rest, patch_null, patch_value, prefixed_key,
+        replace_field, replace_nested, take_field_or, take_rest, write_field,
+    };
+    pub use crate::schema::{is_required, schema_default, schema_non_empty, schema_one_of, schema_range};
+    pub use crate::source::{extract_nested, nested_source, take_nested, unknown_nested};
+    pub use crate::typescript::typescript_module;
+    pub use crate::validate::{check_non_empty, check_one_of, check_range, check_with, validate_field};
+}
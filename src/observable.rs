@@ -0,0 +1,203 @@
+use std::fmt;
+use std::ops::Deref;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{FieldChange, MapStruct, MapToStructError, StateMap};
+
+/// A [`StateMap`] that tells subscribers whenever a tracked key changes, e.g.
+/// to emit an event from a Tauri command that writes a setting:
+///
+/// ```ignore
+/// let mut state = ObservableMap::new(GroomingStateMap::new());
+/// state.subscribe(GroomingRecordKey::SheddingScore, |change| {
+///     if let Some(GroomingRecordValue::SheddingScore(score)) = &change.new {
+///         println!("shedding score is now {}", score);
+///     }
+/// });
+/// state.forward_to(move |change| {
+///     let _ = app.emit("grooming-changed", change);
+/// });
+/// ```
+///
+/// Reads go through to the map; writes have to go through the methods here,
+/// so nothing changes unnoticed.
+pub struct ObservableMap<T: MapStruct> {
+    map: StateMap<T>,
+    subscribers: Vec<Subscriber<T>>,
+    next_id: u64,
+}
+
+/// One key's typed value before and after a write. A side is `None` when the
+/// key was absent there, or held something that doesn't convert.
+pub struct KeyChange<T: MapStruct> {
+    pub key: T::Key,
+    pub old: Option<T::Value>,
+    pub new: Option<T::Value>,
+}
+
+/// Returned by [`ObservableMap::subscribe`] and friends, to
+/// [`unsubscribe`](ObservableMap::unsubscribe) again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type Callback<T> = Box<dyn FnMut(&KeyChange<T>) + Send>;
+
+struct Subscriber<T: MapStruct> {
+    id: SubscriptionId,
+    // `None` for every key
+    key: Option<T::Key>,
+    callback: Callback<T>,
+}
+
+impl<T: MapStruct> ObservableMap<T>
+where
+    T::Key: Copy + PartialEq + fmt::Display,
+    T::Value: Serialize,
+{
+    pub fn new(map: StateMap<T>) -> Self {
+        Self { map, subscribers: Vec::new(), next_id: 0 }
+    }
+
+    pub fn into_map(self) -> StateMap<T> {
+        self.map
+    }
+
+    /// Calls `callback` after every change to `key`.
+    pub fn subscribe(&mut self, key: T::Key, callback: impl FnMut(&KeyChange<T>) + Send + 'static) -> SubscriptionId {
+        self.add_subscriber(Some(key), Box::new(callback))
+    }
+
+    /// Calls `callback` after every change to any tracked key.
+    pub fn subscribe_all(&mut self, callback: impl FnMut(&KeyChange<T>) + Send + 'static) -> SubscriptionId {
+        self.add_subscriber(None, Box::new(callback))
+    }
+
+    /// Hands every change to `emit` as a serializable [`FieldChange`], e.g. a
+    /// closure around Tauri's `app.emit(..)`. A side that's `None` is sent as
+    /// `null`.
+    pub fn forward_to(&mut self, mut emit: impl FnMut(FieldChange) + Send + 'static) -> SubscriptionId {
+        self.subscribe_all(move |change| {
+            emit(FieldChange { key: change.key.to_string(), old: to_value(&change.old), new: to_value(&change.new) })
+        })
+    }
+
+    /// Returns whether the subscription was still there.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let count = self.subscribers.len();
+        self.subscribers.retain(|subscriber| subscriber.id != id);
+        self.subscribers.len() != count
+    }
+
+    /// Writes one typed field, like the generated setters, and notifies if
+    /// the value differs from what was there.
+    pub fn set(&mut self, value: T::Value) {
+        let key = T::value_key(&value);
+        let old = self.read(key);
+        T::write_value(&mut self.map, &value);
+        self.notify(KeyChange { key, old, new: Some(value) });
+    }
+
+    /// Writes a raw value, notifying subscribers of the field that reads
+    /// `key`, if any.
+    pub fn set_raw(&mut self, key: impl Into<String>, value: Value) {
+        let key = key.into();
+        let field = T::key_of(&key);
+        let old = field.and_then(|field| self.read(field));
+        self.map.set(key, value);
+        if let Some(field) = field {
+            let new = self.read(field);
+            self.notify(KeyChange { key: field, old, new });
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        let field = T::key_of(key);
+        let old = field.and_then(|field| self.read(field));
+        let removed = self.map.remove(key);
+        if let Some(field) = field {
+            let new = self.read(field);
+            self.notify(KeyChange { key: field, old, new });
+        }
+        removed
+    }
+
+    /// See [`MapStruct::apply_patch`]; subscribers hear about each key that
+    /// changed, in field order.
+    pub fn apply_patch(&mut self, patch: T::Patch) -> Result<Vec<T::Key>, MapToStructError> {
+        let old: Vec<_> = T::patch_keys(&patch).into_iter().map(|key| (key, self.read(key))).collect();
+        let changed = self.map.apply_patch(patch)?;
+        for (key, old) in old {
+            if changed.contains(&key) {
+                let new = self.read(key);
+                self.notify(KeyChange { key, old, new });
+            }
+        }
+        Ok(changed)
+    }
+
+    fn read(&self, key: T::Key) -> Option<T::Value> {
+        T::read_value(&self.map, key).ok()
+    }
+
+    fn add_subscriber(&mut self, key: Option<T::Key>, callback: Callback<T>) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        self.subscribers.push(Subscriber { id, key, callback });
+        id
+    }
+
+    // Compared as they'd be stored, since the values needn't be `PartialEq`
+    fn notify(&mut self, change: KeyChange<T>) {
+        if to_value(&change.old) == to_value(&change.new) {
+            return;
+        }
+        for subscriber in &mut self.subscribers {
+            if subscriber.key.is_none_or(|key| key == change.key) {
+                (subscriber.callback)(&change);
+            }
+        }
+    }
+}
+
+fn to_value(value: &Option<impl Serialize>) -> Value {
+    value.as_ref().and_then(|value| serde_json::to_value(value).ok()).unwrap_or(Value::Null)
+}
+
+impl<T: MapStruct> Deref for ObservableMap<T> {
+    type Target = StateMap<T>;
+
+    fn deref(&self) -> &StateMap<T> {
+        &self.map
+    }
+}
+
+impl<T: MapStruct> Default for ObservableMap<T>
+where
+    T::Key: Copy + PartialEq + fmt::Display,
+    T::Value: Serialize,
+{
+    fn default() -> Self {
+        Self::new(StateMap::new())
+    }
+}
+
+impl<T: MapStruct> From<StateMap<T>> for ObservableMap<T>
+where
+    T::Key: Copy + PartialEq + fmt::Display,
+    T::Value: Serialize,
+{
+    fn from(map: StateMap<T>) -> Self {
+        Self::new(map)
+    }
+}
+
+impl<T: MapStruct> fmt::Debug for ObservableMap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObservableMap")
+            .field("map", &self.map)
+            .field("subscribers", &self.subscribers.len())
+            .finish()
+    }
+}
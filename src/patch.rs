@@ -0,0 +1,200 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::{self, Impossible};
+use serde::{Deserialize, Serialize, Serializer};
+use specta::datatype::DataType;
+use specta::{Generics, Type, TypeCollection};
+
+// Stands in for an absent key. Like serde's handling of missing struct
+// fields, only `Option<T>` (and `Patch<T>`) accept it.
+pub(crate) struct MissingValue;
+
+const PATCH_TOKEN: &str = "$map_to_struct::Patch";
+
+impl<'de> Deserializer<'de> for MissingValue {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("missing value"))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_none()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if name == PATCH_TOKEN {
+            visitor.visit_unit()
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+/// Tri-state field for callers that need to tell an absent key from an
+/// explicit `null`; a plain `Option<T>` maps both to `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Patch<T> {
+    #[default]
+    Absent,
+    Null,
+    Value(T),
+}
+
+impl<T> Patch<T> {
+    pub fn is_absent(&self) -> bool {
+        matches!(self, Self::Absent)
+    }
+
+    /// `None` when absent, `Some(None)` for null
+    pub fn into_option(self) -> Option<Option<T>> {
+        match self {
+            Self::Absent => None,
+            Self::Null => Some(None),
+            Self::Value(v) => Some(Some(v)),
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patch<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PatchVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for PatchVisitor<T> {
+            type Value = Patch<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an optional value")
+            }
+
+            // Only `MissingValue` calls this
+            fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+                Ok(Patch::Absent)
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+                Ok(Option::<T>::deserialize(d)?.map_or(Patch::Null, Patch::Value))
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(PATCH_TOKEN, PatchVisitor(PhantomData))
+    }
+}
+
+// Absent and null both serialize as `null`, but absent goes through a unit
+// struct so `is_absent` can tell them apart
+impl<T: Serialize> Serialize for Patch<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Absent => serializer.serialize_unit_struct(PATCH_TOKEN),
+            Self::Null => serializer.serialize_none(),
+            Self::Value(v) => serializer.serialize_some(v),
+        }
+    }
+}
+
+// Whether `value` is a `Patch::Absent`, which has no key to be written under
+pub(crate) fn is_absent<T: Serialize + ?Sized>(value: &T) -> bool {
+    value.serialize(AbsentProbe).is_ok()
+}
+
+// Succeeds only for the unit struct `Patch::Absent` serializes as
+struct AbsentProbe;
+
+macro_rules! reject {
+    ($($method:ident($($arg:ident: $ty:ty),*) -> $ok:ty,)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<$ok, Self::Error> {
+                $(let _ = $arg;)*
+                Err(ser::Error::custom("not absent"))
+            }
+        )*
+    };
+}
+
+impl Serializer for AbsentProbe {
+    type Ok = ();
+    type Error = de::value::Error;
+    type SerializeSeq = Impossible<(), Self::Error>;
+    type SerializeTuple = Impossible<(), Self::Error>;
+    type SerializeTupleStruct = Impossible<(), Self::Error>;
+    type SerializeTupleVariant = Impossible<(), Self::Error>;
+    type SerializeMap = Impossible<(), Self::Error>;
+    type SerializeStruct = Impossible<(), Self::Error>;
+    type SerializeStructVariant = Impossible<(), Self::Error>;
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<(), Self::Error> {
+        if name == PATCH_TOKEN {
+            Ok(())
+        } else {
+            Err(ser::Error::custom("not absent"))
+        }
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<(), Self::Error> {
+        Err(ser::Error::custom("not absent"))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, _value: &T) -> Result<(), Self::Error> {
+        Err(ser::Error::custom("not absent"))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Self::Error> {
+        Err(ser::Error::custom("not absent"))
+    }
+
+    reject! {
+        serialize_bool(v: bool) -> (),
+        serialize_i8(v: i8) -> (),
+        serialize_i16(v: i16) -> (),
+        serialize_i32(v: i32) -> (),
+        serialize_i64(v: i64) -> (),
+        serialize_u8(v: u8) -> (),
+        serialize_u16(v: u16) -> (),
+        serialize_u32(v: u32) -> (),
+        serialize_u64(v: u64) -> (),
+        serialize_f32(v: f32) -> (),
+        serialize_f64(v: f64) -> (),
+        serialize_char(v: char) -> (),
+        serialize_str(v: &str) -> (),
+        serialize_bytes(v: &[u8]) -> (),
+        serialize_none() -> (),
+        serialize_unit() -> (),
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str) -> (),
+        serialize_seq(len: Option<usize>) -> Self::SerializeSeq,
+        serialize_tuple(len: usize) -> Self::SerializeTuple,
+        serialize_tuple_struct(name: &'static str, len: usize) -> Self::SerializeTupleStruct,
+        serialize_tuple_variant(
+            name: &'static str, index: u32, variant: &'static str, len: usize
+        ) -> Self::SerializeTupleVariant,
+        serialize_map(len: Option<usize>) -> Self::SerializeMap,
+        serialize_struct(name: &'static str, len: usize) -> Self::SerializeStruct,
+        serialize_struct_variant(
+            name: &'static str, index: u32, variant: &'static str, len: usize
+        ) -> Self::SerializeStructVariant,
+    }
+}
+
+impl<T: Type> Type for Patch<T> {
+    fn inline(type_map: &mut TypeCollection, generics: Generics) -> DataType {
+        <Option<T> as Type>::inline(type_map, generics)
+    }
+}
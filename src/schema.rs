@@ -0,0 +1,411 @@
+use std::ops::{Bound, RangeBounds};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use specta::datatype::{
+    DataType, DataTypeReference, EnumRepr, EnumType, EnumVariants, Field, GenericType, List, LiteralType, NamedFields,
+    PrimitiveType, StructFields,
+};
+use specta::{Type, TypeCollection};
+
+use crate::patch::MissingValue;
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Turns field types into JSON Schema, collecting the named types they use
+/// for `$defs`.
+#[derive(Default)]
+pub struct SchemaBuilder {
+    types: TypeCollection,
+    defs: serde_json::Map<String, Value>,
+}
+
+impl SchemaBuilder {
+    /// The schema of `T`, as described by its `specta::Type` impl.
+    pub fn type_schema<T: Type + ?Sized>(&mut self) -> Value {
+        let ty = T::reference(&mut self.types, &[]).inner;
+        self.convert(&ty, &[])
+    }
+
+    /// A schema any value matches, for fields without `specta::Type`.
+    pub fn any(&self) -> Value {
+        json!({})
+    }
+
+    /// The root schema for `map`, with the named types it refers to.
+    pub fn finish(self, map: MapSchema) -> Value {
+        let mut schema = json!({ "$schema": DRAFT });
+        if let (Value::Object(root), Value::Object(object)) = (&mut schema, map.into_value()) {
+            root.extend(object);
+            if !self.defs.is_empty() {
+                root.insert("$defs".to_string(), Value::Object(self.defs));
+            }
+        }
+        schema
+    }
+
+    fn convert(&mut self, ty: &DataType, generics: &[(GenericType, Value)]) -> Value {
+        match ty {
+            DataType::Any | DataType::Unknown => json!({}),
+            DataType::Primitive(primitive) => primitive_schema(primitive),
+            DataType::Literal(literal) => literal_schema(literal),
+            DataType::List(list) => self.list_schema(list, generics),
+            DataType::Map(map) => json!({
+                "type": "object",
+                "additionalProperties": self.convert(map.value_ty(), generics),
+            }),
+            DataType::Nullable(inner) => json!({ "anyOf": [self.convert(inner, generics), { "type": "null" }] }),
+            DataType::Struct(s) => match s.fields() {
+                StructFields::Unit => json!({ "type": "null" }),
+                StructFields::Unnamed(fields) => self.unnamed_fields(fields.fields(), generics),
+                StructFields::Named(fields) => self.named_fields(fields, generics),
+            },
+            DataType::Enum(e) => self.enum_schema(e, generics),
+            DataType::Tuple(tuple) => {
+                let elements: Vec<_> = tuple.elements().iter().map(|ty| self.convert(ty, generics)).collect();
+                tuple_schema(elements)
+            }
+            DataType::Reference(reference) => self.reference(reference, generics),
+            DataType::Generic(generic) => {
+                generics.iter().find(|(g, _)| g == generic).map_or_else(|| json!({}), |(_, schema)| schema.clone())
+            }
+        }
+    }
+
+    // Plain named types go in `$defs` once; generic ones are inlined with
+    // their arguments filled in
+    fn reference(&mut self, reference: &DataTypeReference, generics: &[(GenericType, Value)]) -> Value {
+        let args: Vec<_> =
+            reference.generics().iter().map(|(g, ty)| (g.clone(), self.convert(ty, generics))).collect();
+        let Some(definition) = self.types.get(reference.sid()).map(|named| named.inner.clone()) else {
+            return json!({});
+        };
+        if !args.is_empty() {
+            return self.convert(&definition, &args);
+        }
+        let name = reference.name().to_string();
+        if !self.defs.contains_key(&name) {
+            // Claimed before converting, so a recursive type refers to itself
+            self.defs.insert(name.clone(), json!({}));
+            let schema = self.convert(&definition, &[]);
+            self.defs.insert(name.clone(), schema);
+        }
+        json!({ "$ref": format!("#/$defs/{}", name) })
+    }
+
+    fn list_schema(&mut self, list: &List, generics: &[(GenericType, Value)]) -> Value {
+        let mut schema = json!({ "type": "array", "items": self.convert(list.ty(), generics) });
+        if let Some(length) = list.length() {
+            keyword(&mut schema, "minItems", json!(length));
+            keyword(&mut schema, "maxItems", json!(length));
+        }
+        if list.unique() {
+            keyword(&mut schema, "uniqueItems", json!(true));
+        }
+        schema
+    }
+
+    fn named_fields(&mut self, fields: &NamedFields, generics: &[(GenericType, Value)]) -> Value {
+        let mut properties = serde_json::Map::new();
+        let mut required = Vec::new();
+        let mut flattened = Vec::new();
+        for (name, field) in fields.fields() {
+            let Some(ty) = field.ty() else { continue };
+            if field.flatten() {
+                flattened.push(self.convert(ty, generics));
+                continue;
+            }
+            if !field.optional() && !matches!(ty, DataType::Nullable(_)) {
+                required.push(name.to_string());
+            }
+            properties.insert(name.to_string(), self.convert(ty, generics));
+        }
+        let mut schema = json!({ "type": "object", "properties": properties });
+        if !required.is_empty() {
+            keyword(&mut schema, "required", json!(required));
+        }
+        if !flattened.is_empty() {
+            keyword(&mut schema, "allOf", json!(flattened));
+        }
+        schema
+    }
+
+    // A newtype is its inner value, anything longer an array
+    fn unnamed_fields(&mut self, fields: &[Field], generics: &[(GenericType, Value)]) -> Value {
+        let mut elements: Vec<_> = fields.iter().filter_map(Field::ty).map(|ty| self.convert(ty, generics)).collect();
+        if elements.len() == 1 {
+            return elements.remove(0);
+        }
+        tuple_schema(elements)
+    }
+
+    fn enum_schema(&mut self, e: &EnumType, generics: &[(GenericType, Value)]) -> Value {
+        let variants: Vec<_> = e.variants().iter().filter(|(_, variant)| !variant.skip()).collect();
+        let unit = variants.iter().all(|(_, variant)| matches!(variant.inner(), EnumVariants::Unit));
+        if unit && *e.repr() == EnumRepr::External {
+            let names: Vec<_> = variants.iter().map(|(name, _)| name.to_string()).collect();
+            return json!({ "type": "string", "enum": names });
+        }
+
+        let schemas: Vec<_> = variants
+            .into_iter()
+            .map(|(name, variant)| {
+                let content = match variant.inner() {
+                    EnumVariants::Unit => None,
+                    EnumVariants::Named(fields) => Some(self.named_fields(fields, generics)),
+                    EnumVariants::Unnamed(fields) => Some(self.unnamed_fields(fields.fields(), generics)),
+                };
+                match (e.repr(), content) {
+                    (EnumRepr::External, None) => json!({ "const": name }),
+                    (EnumRepr::External, Some(content)) => tagged_object(name, content, &[]),
+                    (EnumRepr::Untagged, content) => content.unwrap_or_else(|| json!({ "type": "null" })),
+                    (EnumRepr::Internal { tag }, content) => {
+                        let tag = tagged_object(tag, json!({ "const": name }), &[]);
+                        match content {
+                            Some(content) => json!({ "allOf": [tag, content] }),
+                            None => tag,
+                        }
+                    }
+                    (EnumRepr::Adjacent { tag, content: key }, content) => {
+                        let tag = (tag.as_ref(), json!({ "const": name }));
+                        match content {
+                            Some(content) => tagged_object(key, content, &[tag]),
+                            None => tagged_object(tag.0, tag.1, &[]),
+                        }
+                    }
+                }
+            })
+            .collect();
+        json!({ "anyOf": schemas })
+    }
+}
+
+// An object requiring `key` (and each of `more`) to match its schema
+fn tagged_object(key: &str, schema: Value, more: &[(&str, Value)]) -> Value {
+    let mut properties = serde_json::Map::new();
+    properties.insert(key.to_string(), schema);
+    properties.extend(more.iter().map(|(key, schema)| (key.to_string(), schema.clone())));
+    let required: Vec<_> = properties.keys().cloned().collect();
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn tuple_schema(elements: Vec<Value>) -> Value {
+    // `()` serializes as `null`
+    if elements.is_empty() {
+        return json!({ "type": "null" });
+    }
+    json!({ "type": "array", "prefixItems": elements, "items": false, "minItems": elements.len() })
+}
+
+fn primitive_schema(primitive: &PrimitiveType) -> Value {
+    let (min, max) = match primitive {
+        PrimitiveType::i8 => (json!(i8::MIN), json!(i8::MAX)),
+        PrimitiveType::i16 => (json!(i16::MIN), json!(i16::MAX)),
+        PrimitiveType::i32 => (json!(i32::MIN), json!(i32::MAX)),
+        PrimitiveType::i64 | PrimitiveType::isize => (json!(i64::MIN), json!(i64::MAX)),
+        PrimitiveType::u8 => (json!(0), json!(u8::MAX)),
+        PrimitiveType::u16 => (json!(0), json!(u16::MAX)),
+        PrimitiveType::u32 => (json!(0), json!(u32::MAX)),
+        PrimitiveType::u64 | PrimitiveType::usize => (json!(0), json!(u64::MAX)),
+        PrimitiveType::i128 => return json!({ "type": "integer" }),
+        PrimitiveType::u128 => return json!({ "type": "integer", "minimum": 0 }),
+        PrimitiveType::f32 | PrimitiveType::f64 => return json!({ "type": "number" }),
+        PrimitiveType::bool => return json!({ "type": "boolean" }),
+        PrimitiveType::char => return json!({ "type": "string", "minLength": 1, "maxLength": 1 }),
+        PrimitiveType::String => return json!({ "type": "string" }),
+    };
+    json!({ "type": "integer", "minimum": min, "maximum": max })
+}
+
+fn literal_schema(literal: &LiteralType) -> Value {
+    let value = match literal {
+        LiteralType::i8(v) => json!(v),
+        LiteralType::i16(v) => json!(v),
+        LiteralType::i32(v) => json!(v),
+        LiteralType::u8(v) => json!(v),
+        LiteralType::u16(v) => json!(v),
+        LiteralType::u32(v) => json!(v),
+        LiteralType::f32(v) => json!(v),
+        LiteralType::f64(v) => json!(v),
+        LiteralType::bool(v) => json!(v),
+        LiteralType::String(v) => json!(v),
+        LiteralType::char(v) => json!(v),
+        LiteralType::None => return json!({ "type": "null" }),
+        _ => return json!({}),
+    };
+    json!({ "const": value })
+}
+
+fn keyword(schema: &mut Value, name: &str, value: Value) {
+    if let Value::Object(schema) = schema {
+        schema.insert(name.to_string(), value);
+    }
+}
+
+// Keys that must all be present, plus at least one alternative from each
+// group: a key or any of its aliases, a nested object or its dotted keys
+#[derive(Clone, Default)]
+struct Required {
+    keys: Vec<String>,
+    any_of: Vec<Vec<Required>>,
+}
+
+impl Required {
+    fn key(key: &str) -> Self {
+        Self { keys: vec![key.to_string()], any_of: Vec::new() }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.any_of.is_empty()
+    }
+
+    fn prefixed(&self, prefix: &str) -> Self {
+        Self {
+            keys: self.keys.iter().map(|key| format!("{}.{}", prefix, key)).collect(),
+            any_of: self
+                .any_of
+                .iter()
+                .map(|group| group.iter().map(|required| required.prefixed(prefix)).collect())
+                .collect(),
+        }
+    }
+
+    fn apply(&self, schema: &mut Value) {
+        if !self.keys.is_empty() {
+            keyword(schema, "required", json!(self.keys));
+        }
+        if !self.any_of.is_empty() {
+            let groups: Vec<_> = self
+                .any_of
+                .iter()
+                .map(|group| {
+                    let alternatives: Vec<_> = group
+                        .iter()
+                        .map(|required| {
+                            let mut schema = json!({});
+                            required.apply(&mut schema);
+                            schema
+                        })
+                        .collect();
+                    json!({ "anyOf": alternatives })
+                })
+                .collect();
+            keyword(schema, "allOf", json!(groups));
+        }
+    }
+}
+
+/// The keys of one map (or nested struct) and what they may hold.
+pub struct MapSchema {
+    title: String,
+    properties: serde_json::Map<String, Value>,
+    required: Required,
+    closed: bool,
+}
+
+impl MapSchema {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            properties: serde_json::Map::new(),
+            required: Required::default(),
+            closed: false,
+        }
+    }
+
+    /// A plain field read from `key` or, failing that, one of `aliases`.
+    pub fn field(&mut self, key: &str, aliases: &[&str], schema: Value, required: bool) {
+        for alias in aliases {
+            let mut schema = schema.clone();
+            keyword(&mut schema, "deprecated", json!(true));
+            self.properties.insert(alias.to_string(), schema);
+        }
+        self.properties.insert(key.to_string(), schema);
+        if !required {
+            return;
+        }
+        if aliases.is_empty() {
+            self.required.keys.push(key.to_string());
+        } else {
+            let group = std::iter::once(key).chain(aliases.iter().copied()).map(Required::key).collect();
+            self.required.any_of.push(group);
+        }
+    }
+
+    /// A nested struct, held either as an object under `key` or as dotted
+    /// `key.field` entries.
+    pub fn nested(&mut self, key: &str, nested: MapSchema) {
+        for (field, schema) in &nested.properties {
+            self.properties.insert(format!("{}.{}", key, field), schema.clone());
+        }
+        if !nested.required.is_empty() {
+            self.required.any_of.push(vec![Required::key(key), nested.required.prefixed(key)]);
+        }
+        self.properties.insert(key.to_string(), nested.into_value());
+    }
+
+    /// The `__version` written by a versioned struct.
+    pub fn version(&mut self, version: u32) {
+        let schema = json!({ "type": "integer", "minimum": 0, "maximum": version });
+        self.properties.insert(crate::VERSION_KEY.to_string(), schema);
+    }
+
+    /// Rejects keys that aren't listed, for `deny_unknown_keys`.
+    pub fn deny_unknown_keys(&mut self) {
+        self.closed = true;
+    }
+
+    fn into_value(self) -> Value {
+        let mut schema = json!({ "title": self.title, "type": "object", "properties": self.properties });
+        self.required.apply(&mut schema);
+        if self.closed {
+            keyword(&mut schema, "additionalProperties", json!(false));
+        }
+        schema
+    }
+}
+
+// Whether a missing key fails to convert, i.e. `T` isn't an `Option` or `Patch`
+pub fn is_required<T>() -> bool
+where
+    T: for<'de> Deserialize<'de>,
+{
+    T::deserialize(MissingValue).is_err()
+}
+
+// `range(..)` as `minimum`/`maximum` and their exclusive forms
+pub fn schema_range<T: Serialize, R: RangeBounds<T>>(schema: &mut Value, range: R) {
+    match range.start_bound() {
+        Bound::Included(min) => keyword(schema, "minimum", json!(min)),
+        Bound::Excluded(min) => keyword(schema, "exclusiveMinimum", json!(min)),
+        Bound::Unbounded => {}
+    }
+    match range.end_bound() {
+        Bound::Included(max) => keyword(schema, "maximum", json!(max)),
+        Bound::Excluded(max) => keyword(schema, "exclusiveMaximum", json!(max)),
+        Bound::Unbounded => {}
+    }
+}
+
+// `one_of(..)` as `enum`
+pub fn schema_one_of<A: Serialize>(schema: &mut Value, allowed: &[A]) {
+    keyword(schema, "enum", json!(allowed));
+}
+
+// `non_empty` as the minimum that fits the schema's type
+pub fn schema_non_empty(schema: &mut Value) {
+    let name = match schema.get("type").and_then(Value::as_str) {
+        Some("string") => "minLength",
+        Some("array") => "minItems",
+        Some("object") => "minProperties",
+        _ => return,
+    };
+    keyword(schema, name, json!(1));
+}
+
+// A field's default, as written by `from_typed()`
+pub fn schema_default<T: Serialize>(schema: &mut Value, default: &T) {
+    if let Ok(default) = serde_json::to_value(default) {
+        keyword(schema, "default", default);
+    }
+}
@@ -0,0 +1,144 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{MapStruct, MapToStructError, ValueMap};
+
+/// Read access to wherever a struct's fields live: the state map itself, a
+/// nested JSON object, or the `prefix.*` keys of a parent map.
+pub trait ValueSource {
+    fn get_value(&self, key: &str) -> Option<&Value>;
+
+    /// Every key present, for a `rest` field to pick the unread ones from.
+    fn source_keys(&self) -> Box<dyn Iterator<Item = &str> + '_>;
+}
+
+impl<M: ValueMap + ?Sized> ValueSource for M {
+    fn get_value(&self, key: &str) -> Option<&Value> {
+        self.get(key)
+    }
+
+    fn source_keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        self.keys()
+    }
+}
+
+// Where a `nested` field's own fields are read from
+pub enum NestedSource<'a> {
+    Object(&'a serde_json::Map<String, Value>),
+    Dotted { parent: &'a dyn ValueSource, prefix: &'a str },
+}
+
+impl ValueSource for NestedSource<'_> {
+    fn get_value(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Object(object) => object.get(key),
+            Self::Dotted { parent, prefix } => parent.get_value(&format!("{}.{}", prefix, key)),
+        }
+    }
+
+    fn source_keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        match self {
+            Self::Object(object) => Box::new(object.keys().map(String::as_str)),
+            Self::Dotted { parent, prefix } => Box::new(
+                parent.source_keys().filter_map(|key| key.strip_prefix(*prefix)?.strip_prefix('.')),
+            ),
+        }
+    }
+}
+
+// An object under `key` wins; otherwise the `key.*` entries are used
+pub fn nested_source<'a>(
+    source: &'a dyn ValueSource,
+    key: &'a str,
+) -> Result<NestedSource<'a>, MapToStructError> {
+    match source.get_value(key) {
+        Some(Value::Object(object)) => Ok(NestedSource::Object(object)),
+        Some(other) => Err(MapToStructError::Invalid {
+            key: key.to_string(),
+            expected_type: "object",
+            source: serde_json::Map::<String, Value>::deserialize(other).unwrap_err(),
+        }),
+        None => Ok(NestedSource::Dotted { parent: source, prefix: key }),
+    }
+}
+
+// A nested field's unknown keys, read like `nested_source`: those inside its
+// object, or among its dotted entries when there's no object. Dotted entries
+// next to an object are never read, so they're unknown too.
+pub fn unknown_nested<T: MapStruct>(source: &dyn ValueSource, prefix: &str, key: &str, unknown: &mut Vec<String>) {
+    let nested_prefix = format!("{}{}.", prefix, key);
+    let dotted = NestedSource::Dotted { parent: source, prefix: key };
+    match source.get_value(key) {
+        Some(Value::Object(object)) => {
+            T::unknown_source_keys(&NestedSource::Object(object), &nested_prefix, unknown);
+            unknown.extend(dotted.source_keys().map(|key| format!("{}{}", nested_prefix, key)));
+        }
+        // Not an object, which converting reports
+        Some(_) => {}
+        None => T::unknown_source_keys(&dotted, &nested_prefix, unknown),
+    }
+}
+
+// A map that may be unsized, e.g. `dyn ValueMap`, as a `&dyn ValueSource`
+pub(crate) struct MapSource<'a, M: ?Sized>(pub &'a M);
+
+impl<M: ValueMap + ?Sized> ValueSource for MapSource<'_, M> {
+    fn get_value(&self, key: &str) -> Option<&Value> {
+        self.0.get(key)
+    }
+
+    fn source_keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        self.0.keys()
+    }
+}
+
+pub fn extract_nested<T: MapStruct>(source: &dyn ValueSource, key: &str) -> Result<T, MapToStructError> {
+    T::extract(&nested_source(source, key)?).map_err(|e| e.prefixed(key))
+}
+
+// The `prefix.*` entries of a map, with the prefix stripped, for moving a
+// nested struct's values out of its parent
+struct DottedMap<'a> {
+    parent: &'a mut dyn ValueMap,
+    prefix: &'a str,
+}
+
+impl DottedMap<'_> {
+    fn full_key(&self, key: &str) -> String {
+        format!("{}.{}", self.prefix, key)
+    }
+}
+
+impl ValueMap for DottedMap<'_> {
+    fn get(&self, key: &str) -> Option<&Value> {
+        self.parent.get(&self.full_key(key))
+    }
+
+    fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        let key = self.full_key(&key);
+        self.parent.insert(key, value)
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Value> {
+        let key = self.full_key(key);
+        self.parent.remove(&key)
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        let prefix = self.prefix;
+        Box::new(self.parent.keys().filter_map(move |key| key.strip_prefix(prefix)?.strip_prefix('.')))
+    }
+}
+
+// `extract_nested`, moving the values out of `map`
+pub fn take_nested<T: MapStruct>(map: &mut dyn ValueMap, key: &str) -> Result<T, MapToStructError> {
+    let taken = match map.remove(key) {
+        Some(Value::Object(mut object)) => T::take(&mut object),
+        Some(other) => {
+            let error = serde_json::from_value::<serde_json::Map<String, Value>>(other).unwrap_err();
+            return Err(MapToStructError::Invalid { key: key.to_string(), expected_type: "object", source: error });
+        }
+        None => T::take(&mut DottedMap { parent: map, prefix: key }),
+    };
+    taken.map_err(|e| e.prefixed(key))
+}
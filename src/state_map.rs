@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use specta::datatype::DataType;
+use specta::{Generics, Type, TypeCollection};
+
+use crate::{FieldChange, MapStruct, MapToStructError, MapToStructReport, ValueMap};
+
+/// A `HashMap<String, Value>` holding the keys of `T`, which serializes as
+/// the bare map. Use it in place of a hand-written wrapper:
+///
+/// ```ignore
+/// pub type GroomingStateMap = StateMap<GroomingRecord>;
+/// ```
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StateMap<T> {
+    entries: HashMap<String, Value>,
+    #[serde(skip)]
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> StateMap<T> {
+    pub fn new() -> Self {
+        Self::from_map(HashMap::new())
+    }
+
+    pub fn from_map(entries: HashMap<String, Value>) -> Self {
+        Self { entries, marker: PhantomData }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.get(key)
+    }
+
+    pub fn set(&mut self, key: impl Into<String>, value: Value) {
+        self.entries.insert(key.into(), value);
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.entries.remove(key)
+    }
+
+    pub fn as_map(&self) -> &HashMap<String, Value> {
+        &self.entries
+    }
+
+    pub fn into_map(self) -> HashMap<String, Value> {
+        self.entries
+    }
+}
+
+impl<T: MapStruct> StateMap<T> {
+    pub fn to_typed(&self) -> Result<T, MapToStructError> {
+        T::from_map(&self.entries)
+    }
+
+    /// [`to_typed`](Self::to_typed) without copying: the values are moved
+    /// out of the map.
+    pub fn into_typed(self) -> Result<T, MapToStructError> {
+        T::from_map_owned(self.entries)
+    }
+
+    pub fn to_typed_report(&self) -> MapToStructReport<T> {
+        T::from_map_report(&self.entries)
+    }
+
+    /// Keys that no field of `T` reads, e.g. left behind by a rename.
+    pub fn unknown_keys(&self) -> Vec<String> {
+        T::unknown_keys(&self.entries)
+    }
+
+    /// Migrates the map in place to `T`'s current version, so the upgrade
+    /// can be persisted. Returns whether anything ran.
+    pub fn upgrade(&mut self) -> Result<bool, MapToStructError> {
+        T::upgrade(&mut self.entries)
+    }
+
+    /// See [`MapStruct::apply_patch`].
+    pub fn apply_patch(&mut self, patch: T::Patch) -> Result<Vec<T::Key>, MapToStructError> {
+        T::apply_patch(&mut self.entries, patch)
+    }
+
+    /// The keys of `T` whose values differ from `other`'s.
+    pub fn diff(&self, other: &Self) -> Vec<FieldChange> {
+        T::diff_maps(&self.entries, &other.entries)
+    }
+
+    /// See [`MapStruct::json_schema`].
+    pub fn json_schema() -> Value {
+        T::json_schema()
+    }
+
+    pub fn from_typed(typed: &T) -> Self {
+        let mut entries = HashMap::new();
+        typed.insert_map(&mut entries);
+        Self::from_map(entries)
+    }
+}
+
+impl<T: MapStruct> From<T> for StateMap<T> {
+    fn from(typed: T) -> Self {
+        Self::from_typed(&typed)
+    }
+}
+
+impl<T> From<HashMap<String, Value>> for StateMap<T> {
+    fn from(entries: HashMap<String, Value>) -> Self {
+        Self::from_map(entries)
+    }
+}
+
+impl<T> ValueMap for StateMap<T> {
+    fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.get(key)
+    }
+
+    fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        self.entries.insert(key, value)
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Value> {
+        self.entries.remove(key)
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        ValueMap::keys(&self.entries)
+    }
+}
+
+// Hand-written so `T` itself needn't be `Clone`, `Debug`, ...
+impl<T> Clone for StateMap<T> {
+    fn clone(&self) -> Self {
+        Self::from_map(self.entries.clone())
+    }
+}
+
+impl<T> Default for StateMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> PartialEq for StateMap<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl<T> fmt::Debug for StateMap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StateMap").field(&self.entries).finish()
+    }
+}
+
+impl<T: MapStruct> Type for StateMap<T> {
+    fn inline(type_map: &mut TypeCollection, generics: Generics) -> DataType {
+        T::map_type(type_map, generics)
+    }
+}
@@ -0,0 +1,69 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use specta::datatype::{DataType, FunctionResultVariant};
+use specta::TypeCollection;
+use specta_typescript::{ExportError, Typescript};
+
+// The module `MapStruct::typescript` returns: the named types the fields
+// use, then the record with one key per variant of the `…Key` enum
+// (optional where a missing key still converts), its key union, the patch
+// type and typed get/set wrappers around the caller's `invoke`
+pub fn typescript_module(
+    config: &Typescript,
+    name: &str,
+    types: &TypeCollection,
+    fields: Vec<(Cow<'static, str>, DataType, bool)>,
+) -> Result<String, ExportError> {
+    let mut out = config.export(types)?;
+
+    writeln!(out, "/** The keys of a `{}` state map and the values they hold */", name)?;
+    writeln!(out, "export type {} = {{", name)?;
+    for (key, ty, required) in &fields {
+        let ty = specta_typescript::datatype(config, &FunctionResultVariant::Value(ty.clone()), types)?;
+        let optional = if *required { "" } else { "?" };
+        writeln!(out, "\t{}{}: {};", property_name(key), optional, ty)?;
+    }
+    writeln!(out, "}};\n")?;
+
+    let keys: Vec<_> = fields.iter().map(|(key, ..)| string_literal(key)).collect();
+    let keys = if keys.is_empty() { "never".to_string() } else { keys.join(" | ") };
+    writeln!(out, "/** A single key of `{}` */", name)?;
+    writeln!(out, "export type {}Key = {};\n", name, keys)?;
+
+    writeln!(out, "/** Any subset of `{}`, for partial updates */", name)?;
+    writeln!(out, "export type {0}Patch = Partial<{0}>;\n", name)?;
+
+    writeln!(out, "/** Sends a command to the backend, e.g. Tauri's `invoke` */")?;
+    writeln!(out, "export type Invoke = (command: string, args?: Record<string, unknown>) => Promise<unknown>;\n")?;
+
+    writeln!(out, "/** Typed access to single keys through a `get` command taking `{{ key }}` and a `set` command taking `{{ key, value }}` */")?;
+    writeln!(out, "export function {}State(invoke: Invoke, commands: {{ get: string; set: string }}) {{", lower_first(name))?;
+    writeln!(out, "\treturn {{")?;
+    writeln!(out, "\t\tget: <K extends {0}Key>(key: K) => invoke(commands.get, {{ key }}) as Promise<{0}[K]>,", name)?;
+    writeln!(out, "\t\tset: <K extends {0}Key>(key: K, value: {0}[K]) => invoke(commands.set, {{ key, value }}) as Promise<void>,", name)?;
+    writeln!(out, "\t}};")?;
+    writeln!(out, "}}")?;
+    Ok(out)
+}
+
+fn string_literal(key: &str) -> String {
+    serde_json::to_string(key).unwrap_or_else(|_| format!("\"{}\"", key))
+}
+
+// Keys that aren't identifiers, e.g. `font-size`, need quoting
+fn property_name(key: &str) -> Cow<'_, str> {
+    let mut chars = key.chars();
+    let identifier = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if identifier {
+        Cow::Borrowed(key)
+    } else {
+        Cow::Owned(string_literal(key))
+    }
+}
+
+fn lower_first(name: &str) -> String {
+    let mut chars = name.chars();
+    chars.next().map(|first| first.to_lowercase().chain(chars).collect()).unwrap_or_default()
+}
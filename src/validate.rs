@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::RangeBounds;
+
+use crate::MapToStructError;
+
+// Validation attributes run once the value has deserialized
+pub fn validate_field<T>(
+    result: Result<T, MapToStructError>,
+    checks: impl FnOnce(&T) -> Result<(), MapToStructError>,
+) -> Result<T, MapToStructError> {
+    let value = result?;
+    checks(&value)?;
+    Ok(value)
+}
+
+fn validation_error(key: &str, message: impl fmt::Display) -> MapToStructError {
+    MapToStructError::Validation { key: key.to_string(), message: message.to_string() }
+}
+
+// `range(0..=10)`
+pub fn check_range<T, R>(key: &str, value: &T, range: R) -> Result<(), MapToStructError>
+where
+    T: PartialOrd + fmt::Debug,
+    R: RangeBounds<T> + fmt::Debug,
+{
+    if range.contains(value) {
+        Ok(())
+    } else {
+        Err(validation_error(key, format_args!("{:?} is outside {:?}", value, range)))
+    }
+}
+
+// `non_empty`
+pub fn check_non_empty<T: IsEmpty + ?Sized>(key: &str, value: &T) -> Result<(), MapToStructError> {
+    if IsEmpty::is_empty(value) {
+        Err(validation_error(key, "must not be empty"))
+    } else {
+        Ok(())
+    }
+}
+
+// `one_of("slicker", "pin", "metal")`
+pub fn check_one_of<T, A>(key: &str, value: &T, allowed: &[A]) -> Result<(), MapToStructError>
+where
+    T: PartialEq<A> + fmt::Debug,
+    A: fmt::Debug,
+{
+    if allowed.iter().any(|a| value == a) {
+        Ok(())
+    } else {
+        Err(validation_error(key, format_args!("{:?} is not one of {:?}", value, allowed)))
+    }
+}
+
+// `validate(path::to::check)` where `check(&T) -> Result<(), impl Display>`
+pub fn check_with<T, E>(key: &str, value: &T, check: impl FnOnce(&T) -> Result<(), E>) -> Result<(), MapToStructError>
+where
+    E: fmt::Display,
+{
+    check(value).map_err(|message| validation_error(key, message))
+}
+
+/// Types the `non_empty` attribute applies to
+pub trait IsEmpty {
+    fn is_empty(&self) -> bool;
+}
+
+impl IsEmpty for str {
+    fn is_empty(&self) -> bool {
+        str::is_empty(self)
+    }
+}
+
+impl IsEmpty for String {
+    fn is_empty(&self) -> bool {
+        String::is_empty(self)
+    }
+}
+
+impl<T> IsEmpty for Vec<T> {
+    fn is_empty(&self) -> bool {
+        Vec::is_empty(self)
+    }
+}
+
+impl<K, V> IsEmpty for HashMap<K, V> {
+    fn is_empty(&self) -> bool {
+        HashMap::is_empty(self)
+    }
+}
+
+impl<K, V> IsEmpty for BTreeMap<K, V> {
+    fn is_empty(&self) -> bool {
+        BTreeMap::is_empty(self)
+    }
+}
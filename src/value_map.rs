@@ -0,0 +1,90 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+
+use serde_json::Value;
+
+/// A string-keyed map of JSON values that state can be converted from and
+/// written back into. Wrappers name theirs with `#[map_to_struct(map = ..)]`.
+pub trait ValueMap {
+    fn get(&self, key: &str) -> Option<&Value>;
+
+    fn insert(&mut self, key: String, value: Value) -> Option<Value>;
+
+    fn remove(&mut self, key: &str) -> Option<Value>;
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &str> + '_>;
+}
+
+impl<S: BuildHasher> ValueMap for HashMap<String, Value, S> {
+    fn get(&self, key: &str) -> Option<&Value> {
+        HashMap::get(self, key)
+    }
+
+    fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        HashMap::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Value> {
+        HashMap::remove(self, key)
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(HashMap::keys(self).map(String::as_str))
+    }
+}
+
+impl ValueMap for BTreeMap<String, Value> {
+    fn get(&self, key: &str) -> Option<&Value> {
+        BTreeMap::get(self, key)
+    }
+
+    fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        BTreeMap::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Value> {
+        BTreeMap::remove(self, key)
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(BTreeMap::keys(self).map(String::as_str))
+    }
+}
+
+impl ValueMap for serde_json::Map<String, Value> {
+    fn get(&self, key: &str) -> Option<&Value> {
+        serde_json::Map::get(self, key)
+    }
+
+    fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        serde_json::Map::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Value> {
+        serde_json::Map::remove(self, key)
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(serde_json::Map::keys(self).map(String::as_str))
+    }
+}
+
+// `shift_remove` keeps the remaining keys in insertion order
+#[cfg(feature = "indexmap")]
+impl<S: BuildHasher> ValueMap for indexmap::IndexMap<String, Value, S> {
+    fn get(&self, key: &str) -> Option<&Value> {
+        indexmap::IndexMap::get(self, key)
+    }
+
+    fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        indexmap::IndexMap::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Value> {
+        indexmap::IndexMap::shift_remove(self, key)
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(indexmap::IndexMap::keys(self).map(String::as_str))
+    }
+}
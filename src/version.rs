@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::field::invalid;
+use crate::{MapStruct, MapToStructError, ValueMap};
+
+/// The key a versioned struct records its `version = N` under.
+pub const VERSION_KEY: &str = "__version";
+
+// The version `map` was written at; maps from before versioning are 0
+fn stored_version<M: ValueMap + ?Sized>(map: &M) -> Result<u32, MapToStructError> {
+    match map.get(VERSION_KEY) {
+        Some(v) => u32::deserialize(v).map_err(|source| invalid::<u32>(VERSION_KEY, source)),
+        None => Ok(0),
+    }
+}
+
+// Whether `T::migrate` has to run before `map` converts. A map from a newer
+// release is an error rather than something to guess at.
+pub(crate) fn is_outdated<T: MapStruct, M: ValueMap + ?Sized>(map: &M) -> Result<bool, MapToStructError> {
+    let Some(current) = T::VERSION else {
+        return Ok(false);
+    };
+    let stored = stored_version(map)?;
+    if stored > current {
+        return Err(MapToStructError::UnsupportedVersion { version: stored, supported: current });
+    }
+    Ok(stored < current)
+}
+
+// Runs each step from the stored version up to `T::VERSION`, in order
+pub(crate) fn upgrade<T: MapStruct>(map: &mut dyn ValueMap) -> Result<bool, MapToStructError> {
+    let (Some(current), true) = (T::VERSION, is_outdated::<T, _>(map)?) else {
+        return Ok(false);
+    };
+    for from in stored_version(map)?..current {
+        T::migrate(from, map);
+    }
+    map.insert(VERSION_KEY.to_string(), current.into());
+    Ok(true)
+}
+
+// An owned copy of a borrowed map, for migrating without touching it
+pub(crate) fn copy_map<M: ValueMap + ?Sized>(map: &M) -> HashMap<String, Value> {
+    map.keys().filter_map(|key| Some((key.to_string(), map.get(key)?.clone()))).collect()
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::*;
+use map_to_struct::specta_typescript::Typescript;
+use map_to_struct::{MapStruct, MapToStruct, StateMap};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use specta::datatype::{DataType, StructFields};
+use specta::{Generics, Type, TypeCollection};
+
+#[derive(Debug, Serialize, Deserialize, Type)]
+enum Coat {
+    Short,
+    Long,
+}
+
+#[derive(Debug, MapToStruct)]
+struct Coats {
+    coat: Coat,
+    #[map_to_struct(default)]
+    history: Vec<Coat>,
+}
+
+#[test]
+fn map_type_is_described_as_a_partial_record() {
+    let DataType::Struct(object) = GroomingStateMap::inline(&mut TypeCollection::default(), Generics::NONE) else {
+        panic!("expected an object type");
+    };
+    let StructFields::Named(fields) = object.fields() else {
+        panic!("expected named fields");
+    };
+    let keys: Vec<_> = fields.fields().iter().map(|(key, _)| key.as_ref()).collect();
+    assert_eq!(keys, ["fur_length_cm", "brush_type", "shedding_score", "nail_trimmed", "favorite_spot"]);
+    assert!(fields.fields().iter().all(|(_, field)| field.optional()));
+
+    let record = SettingsMap::inline(&mut TypeCollection::default(), Generics::NONE);
+    assert!(matches!(record, DataType::Map(_)));
+}
+
+#[test]
+fn json_schema_describes_keys_types_and_checks() {
+    let schema = GroomingStateMap::json_schema();
+    assert_eq!(schema["$schema"], "https://json-schema.org/draft/2020-12/schema");
+    assert_eq!(schema["required"], json!(["fur_length_cm", "brush_type", "favorite_spot"]));
+    let properties = &schema["properties"];
+    assert_eq!(properties["brush_type"], json!({ "type": "string", "enum": ["slicker", "pin", "metal"] }));
+    assert_eq!(properties["shedding_score"], json!({ "type": "integer", "minimum": 0, "maximum": 10, "default": 0 }));
+    assert_eq!(properties["nail_trimmed"], json!({ "type": "boolean", "default": false }));
+    assert_eq!(properties["favorite_spot"], json!({ "type": "string", "minLength": 1 }));
+
+    // Aliases may stand in for the key; strict maps are closed
+    let brush = BrushMap::json_schema();
+    assert_eq!(brush["additionalProperties"], false);
+    assert_eq!(brush["properties"]["brush"]["deprecated"], true);
+    assert_eq!(brush["allOf"][0]["anyOf"][1], json!({ "required": ["brush"] }));
+
+    // Nested structs as an object or dotted keys; `record` maps leave types open
+    let settings = SettingsMap::json_schema();
+    assert_eq!(settings["properties"]["audio"]["required"], json!(["volume", "muted"]));
+    assert_eq!(settings["properties"]["audio.volume"]["maximum"], 255);
+    assert_eq!(settings["allOf"][0]["anyOf"][1], json!({ "required": ["audio.volume", "audio.muted"] }));
+    assert_eq!(settings["properties"]["theme"], json!({}));
+
+    let coats = StateMap::<Coats>::json_schema();
+    assert_eq!(coats["properties"]["history"], json!({ "type": "array", "items": { "$ref": "#/$defs/Coat" }, "default": [] }));
+    assert_eq!(coats["$defs"]["Coat"], json!({ "type": "string", "enum": ["Short", "Long"] }));
+    assert_eq!(coats["required"], json!(["coat"]));
+}
+
+// Typed, so the nested struct is exported by name
+#[derive(Debug, MapToStruct)]
+struct Mixer {
+    #[map_to_struct(nested)]
+    audio: Audio,
+    #[map_to_struct(default)]
+    channels: u8,
+}
+
+#[test]
+fn typescript_module_has_record_keys_patch_and_accessors() {
+    let config = Typescript::default();
+    let module = GroomingRecord::typescript(&config).unwrap();
+    assert!(module.contains("export type GroomingRecord = {\n\tfur_length_cm: number;\n\tbrush_type: string;\n\tshedding_score?: number;"));
+    assert!(module.contains(r#"export type GroomingRecordKey = "fur_length_cm" | "brush_type" | "shedding_score" | "nail_trimmed" | "favorite_spot";"#));
+    assert!(module.contains("export type GroomingRecordPatch = Partial<GroomingRecord>;"));
+    assert!(module.contains("export function groomingRecordState(invoke: Invoke, commands: { get: string; set: string })"));
+    assert!(module.contains("get: <K extends GroomingRecordKey>(key: K) => invoke(commands.get, { key }) as Promise<GroomingRecord[K]>"));
+
+    // Named field types are exported alongside, keys follow the map
+    let coats = Coats::typescript(&config).unwrap();
+    assert!(coats.contains(r#"export type Coat = "Short" | "Long""#));
+    assert!(coats.contains("\thistory?: Coat[];"));
+    assert!(Brush::typescript(&config).unwrap().contains("\tfurLengthCm: number;\n\tkind: string;"));
+
+    // Keys that may be missing are optional, nested fields are their dotted keys
+    assert!(Notes::typescript(&config).unwrap().contains("\ttext?: string | null;\n\tedit?: string | null;"));
+    let mixer = Mixer::typescript(&config).unwrap();
+    assert!(mixer.contains("export type Audio = { volume: number; muted: boolean }"));
+    assert!(mixer.contains("export type Mixer = {\n\taudio: Audio;\n\tchannels?: number;\n};"));
+
+    let path = std::env::temp_dir().join("map_to_struct_tests").join("grooming.ts");
+    GroomingRecord::export_typescript(&config, &path).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), module);
+}
+
+// Each member of the module's `…Key` union, read as the Rust key enum
+fn union_keys<K: DeserializeOwned>(module: &str, name: &str) -> Vec<K> {
+    let prefix = format!("export type {}Key = ", name);
+    let union = module.lines().find_map(|line| line.strip_prefix(prefix.as_str())).unwrap();
+    union.trim_end_matches(';').split(" | ").map(|member| serde_json::from_str(member).unwrap()).collect()
+}
+
+#[test]
+fn typescript_key_unions_match_the_key_enums() {
+    let config = Typescript::default();
+    let keys: Vec<GroomingRecordKey> = union_keys(&GroomingRecord::typescript(&config).unwrap(), "GroomingRecord");
+    assert_eq!(keys, GroomingRecordKey::ALL);
+    let keys: Vec<BrushKey> = union_keys(&Brush::typescript(&config).unwrap(), "Brush");
+    assert_eq!(keys, BrushKey::ALL);
+    let keys: Vec<SettingsKey> = union_keys(&Settings::typescript(&config).unwrap(), "Settings");
+    assert_eq!(keys, SettingsKey::ALL);
+    let keys: Vec<MixerKey> = union_keys(&Mixer::typescript(&config).unwrap(), "Mixer");
+    assert_eq!(keys, MixerKey::ALL);
+}
@@ -0,0 +1,93 @@
+// Types shared by the integration tests; each test file uses some of them
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, HashMap};
+
+use map_to_struct::{map_to_struct, MapToStruct, StateMap};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use specta::Type;
+
+pub type GroomingStateMap = StateMap<GroomingRecord>;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type, MapToStruct)]
+pub struct GroomingRecord {
+    pub fur_length_cm: i32,
+    #[map_to_struct(one_of("slicker", "pin", "metal"))]
+    pub brush_type: String,
+    #[map_to_struct(range(0..=10), default = 0)]
+    pub shedding_score: u8,
+    #[map_to_struct(default)]
+    pub nail_trimmed: bool,
+    #[map_to_struct(non_empty)]
+    pub favorite_spot: String,
+}
+
+pub fn grooming_state() -> GroomingStateMap {
+    let mut map = GroomingStateMap::new();
+    map.set("fur_length_cm", json!(2));
+    map.set("brush_type", json!("slicker"));
+    map.set("shedding_score", json!(7));
+    map.set("nail_trimmed", json!(true));
+    map.set("favorite_spot", json!("chin"));
+    map
+}
+
+#[derive(Debug)]
+pub struct NotesMap(pub HashMap<String, Value>);
+
+map_to_struct! {
+    NotesMap =>
+    #[derive(Debug, Type)]
+    pub struct Notes {
+        #[validate(no_shouting)]
+        pub text: Option<String>,
+        pub edit: map_to_struct::Patch<String>,
+    }
+}
+
+fn no_shouting(text: &Option<String>) -> Result<(), &'static str> {
+    match text {
+        Some(text) if text.chars().any(char::is_lowercase) => Ok(()),
+        Some(_) => Err("no shouting"),
+        None => Ok(()),
+    }
+}
+
+#[derive(Debug)]
+pub struct BrushMap(pub BTreeMap<String, Value>);
+
+map_to_struct! {
+    #[rename_all = "camelCase"]
+    #[strict]
+    BrushMap => #[derive(Debug, Type)] pub struct Brush {
+        pub fur_length_cm: i32,
+        #[key = "kind"]
+        #[alias = "brush"]
+        #[alias = "brush_type"]
+        pub brush_type: String,
+    }
+}
+
+// Only ever nested, so it needs no map type of its own
+#[derive(Debug, PartialEq, Type, MapToStruct)]
+pub struct Audio {
+    pub volume: u8,
+    pub muted: bool,
+}
+
+#[derive(Debug)]
+pub struct SettingsMap(pub HashMap<String, Value>);
+
+map_to_struct! {
+    #[specta = "record"]
+    SettingsMap => #[derive(Debug)] pub struct Settings {
+        #[nested]
+        pub audio: Audio,
+        pub theme: String,
+    }
+}
+
+pub fn settings(entries: Value) -> SettingsMap {
+    SettingsMap(serde_json::from_value(entries).unwrap())
+}
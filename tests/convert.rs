@@ -0,0 +1,276 @@
+mod common;
+
+use std::collections::{BTreeMap, HashMap};
+
+use common::*;
+use map_to_struct::{map_to_struct, MapToStruct, MapToStructError, Patch, StateMap};
+use serde_json::{json, Value};
+use specta::Type;
+
+#[test]
+fn grooming_state_types_match() {
+    let json = serde_json::to_value(grooming_state()).unwrap();
+
+    // Will panic if any type mismatches
+    let _: GroomingRecord = serde_json::from_value(json).unwrap();
+}
+
+#[test]
+fn grooming_state_round_trips_through_typed() {
+    let map = grooming_state();
+    let record = map.to_typed().unwrap();
+    let back = GroomingStateMap::from(record);
+
+    assert_eq!(back, map);
+}
+
+#[test]
+fn into_typed_moves_values_out_with_the_same_result() {
+    let borrowed = serde_json::to_value(grooming_state().to_typed().unwrap()).unwrap();
+    let moved = serde_json::to_value(grooming_state().into_typed().unwrap()).unwrap();
+    assert_eq!(moved, borrowed);
+
+    let mut map = grooming_state();
+    map.set("shedding_score", json!(11));
+    assert!(matches!(map.into_typed(), Err(MapToStructError::Validation { .. })));
+}
+
+#[test]
+fn to_typed_reports_missing_and_invalid_keys() {
+    let mut map = grooming_state();
+    map.remove("brush_type");
+    let err = map.to_typed().unwrap_err();
+    assert!(matches!(&err, MapToStructError::Missing { key } if key == "brush_type"));
+
+    let mut map = grooming_state();
+    map.set("shedding_score", json!("lots"));
+    let err = map.to_typed().unwrap_err();
+    assert!(matches!(&err, MapToStructError::Invalid { key, expected_type: "integer", .. } if key == "shedding_score"));
+    assert_eq!(serde_json::to_value(&err).unwrap()["kind"], "Invalid");
+}
+
+#[test]
+fn invalid_values_name_the_json_kind_expected() {
+    let expected = |err: MapToStructError| match err {
+        MapToStructError::Invalid { expected_type, .. } => expected_type,
+        other => panic!("expected Invalid, got {:?}", other),
+    };
+    let notes = |entries: Value| NotesMap(serde_json::from_value(entries).unwrap()).to_typed().unwrap_err();
+    assert_eq!(expected(notes(json!({ "text": 3 }))), "string");
+    assert_eq!(expected(notes(json!({ "edit": [] }))), "string");
+    assert_eq!(expected(settings(json!({ "audio": 3, "theme": "dark" })).to_typed().unwrap_err()), "object");
+
+    let mut map = grooming_state();
+    map.set("nail_trimmed", json!("yes"));
+    assert_eq!(expected(map.to_typed().unwrap_err()), "boolean");
+    assert_eq!(expected(map_to_struct::extract_field::<Vec<f32>>(&map, "nail_trimmed").unwrap_err()), "array");
+}
+
+#[test]
+fn to_typed_report_collects_every_broken_key() {
+    let mut map = grooming_state();
+    map.remove("brush_type");
+    map.set("shedding_score", json!("lots"));
+    map.set("nail_trimmed", json!(1));
+
+    let report = map.to_typed_report();
+    let broken: Vec<_> = report.errors.iter().map(MapToStructError::key).collect();
+
+    assert!(report.value.is_none());
+    assert_eq!(broken, ["brush_type", "shedding_score", "nail_trimmed"]);
+    assert_eq!(report.converted, ["fur_length_cm", "favorite_spot"]);
+    assert!(grooming_state().to_typed_report().into_result().is_ok());
+}
+
+#[test]
+fn missing_keys_fall_back_to_field_defaults() {
+    let mut map = grooming_state();
+    map.remove("shedding_score");
+    map.remove("nail_trimmed");
+    let record = map.to_typed().unwrap();
+    assert_eq!(record.shedding_score, 0);
+    assert!(!record.nail_trimmed);
+
+    map.set("shedding_score", json!("lots"));
+    assert!(matches!(map.to_typed(), Err(MapToStructError::Invalid { .. })));
+}
+
+#[test]
+fn validation_attributes_reject_out_of_range_values() {
+    let mut map = grooming_state();
+    map.set("shedding_score", json!(11));
+    map.set("brush_type", json!("comb"));
+    map.set("favorite_spot", json!(""));
+
+    let report = map.to_typed_report();
+    let failed: Vec<_> = report.errors.iter().map(|e| (e.key(), e.to_string())).collect();
+    assert_eq!(failed, [
+        ("brush_type", r#"Invalid brush_type: "comb" is not one of ["slicker", "pin", "metal"]"#.to_string()),
+        ("shedding_score", "Invalid shedding_score: 11 is outside 0..=10".to_string()),
+        ("favorite_spot", "Invalid favorite_spot: must not be empty".to_string()),
+    ]);
+}
+
+#[test]
+fn optional_fields_distinguish_absent_null_and_present() {
+    let notes = |entries: Value| NotesMap(serde_json::from_value(entries).unwrap()).to_typed().unwrap();
+
+    let absent = notes(json!({}));
+    assert_eq!((absent.text, absent.edit), (None, Patch::Absent));
+
+    let null = notes(json!({ "text": null, "edit": null }));
+    assert_eq!((null.text, null.edit), (None, Patch::Null));
+
+    let present = notes(json!({ "text": "matted", "edit": "brushed" }));
+    assert_eq!(present.text.as_deref(), Some("matted"));
+    assert_eq!(present.edit, Patch::Value("brushed".to_string()));
+
+    let shouted = NotesMap(serde_json::from_value(json!({ "text": "MATTED" })).unwrap()).to_typed();
+    assert!(matches!(shouted, Err(MapToStructError::Validation { message, .. }) if message == "no shouting"));
+}
+
+#[test]
+fn absent_patch_fields_are_not_written_back() {
+    let notes = |entries: Value| NotesMap(serde_json::from_value(entries).unwrap());
+
+    let absent = notes(json!({})).to_typed().unwrap();
+    let written = NotesMap::from(absent);
+    assert!(!written.0.contains_key("edit"));
+    assert_eq!(written.to_typed().unwrap().edit, Patch::Absent);
+
+    let null = NotesMap::from(notes(json!({ "edit": null })).to_typed().unwrap());
+    assert_eq!(null.0.get("edit"), Some(&Value::Null));
+    assert_eq!(null.to_typed().unwrap().edit, Patch::Null);
+
+    // Setting a field to absent removes its key
+    let mut map = notes(json!({ "edit": "brushed" }));
+    map.set_edit(Patch::Absent);
+    assert!(!map.0.contains_key("edit"));
+}
+
+// A wrapper that keeps more than the entries
+#[derive(Debug, Default)]
+struct ProfileState {
+    entries: serde_json::Map<String, Value>,
+    dirty: bool,
+}
+
+#[derive(Debug, PartialEq, Type, MapToStruct)]
+#[map_to_struct(map = ProfileState, field = entries)]
+struct Profile {
+    name: String,
+}
+
+#[test]
+fn named_wrappers_convert_the_field_holding_the_entries() {
+    let state = ProfileState { entries: serde_json::from_value(json!({ "name": "Miso" })).unwrap(), dirty: true };
+    let profile = state.to_typed().unwrap();
+    assert_eq!(profile.name, "Miso");
+
+    let written = ProfileState::from(profile);
+    assert_eq!(Value::Object(written.entries), json!({ "name": "Miso" }));
+    assert!(!written.dirty);
+}
+
+// Can't derive `Type`: the rest holds raw `Value`s
+#[derive(Debug, MapToStruct)]
+struct Theme {
+    name: String,
+    #[map_to_struct(rest)]
+    extra: BTreeMap<String, Value>,
+}
+
+#[test]
+fn rest_field_keeps_unread_keys_and_writes_them_back() {
+    let state = StateMap::<Theme>::from(serde_json::from_value::<HashMap<String, Value>>(json!({
+        "name": "dark", "accent": "teal", "font.size": 14
+    })).unwrap());
+    let theme = state.to_typed().unwrap();
+    assert_eq!(theme.name, "dark");
+    assert_eq!(theme.extra, BTreeMap::from([("accent".to_string(), json!("teal")), ("font.size".to_string(), json!(14))]));
+    assert!(state.unknown_keys().is_empty());
+
+    assert_eq!(StateMap::from(theme), state);
+    assert_eq!(state.into_typed().unwrap().extra.len(), 2);
+}
+
+#[test]
+fn nested_structs_read_objects_or_dotted_keys() {
+    let expected = Audio { volume: 7, muted: false };
+
+    let object = settings(json!({ "audio": { "volume": 7, "muted": false }, "theme": "dark" }));
+    assert_eq!(object.to_typed().unwrap().audio, expected);
+
+    let dotted = settings(json!({ "audio.volume": 7, "audio.muted": false, "theme": "dark" }));
+    let typed = dotted.to_typed().unwrap();
+    assert_eq!(typed.audio, expected);
+    assert_eq!(SettingsMap::from(typed).0, dotted.0);
+
+    let mut object = object;
+    object.set_audio(Audio { volume: 2, muted: true });
+    assert_eq!(object.audio().unwrap(), Audio { volume: 2, muted: true });
+    assert!(!object.0.contains_key("audio"));
+
+    let patch = SettingsPatch { audio: Some(Audio { volume: 2, muted: false }), theme: Some("dark".to_string()) };
+    assert_eq!(object.apply_patch(patch).unwrap(), [SettingsKey::Audio]);
+    assert_eq!(object.0.get("audio.muted"), Some(&json!(false)));
+
+    assert_eq!(dotted.into_typed().unwrap().audio, expected);
+    let moved = settings(json!({ "audio": { "volume": 7, "muted": false }, "theme": "dark" })).into_typed();
+    assert_eq!(moved.unwrap().audio, expected);
+
+    let stale = settings(json!({ "audio.volume": 7, "audio.bass": 3, "theme": "dark" }));
+    assert_eq!(stale.unknown_keys(), ["audio.bass"]);
+
+    let broken = settings(json!({ "audio": { "volume": "loud" } }));
+    let keys: Vec<_> = broken.to_typed_report().errors.iter().map(|e| e.key().to_string()).collect();
+    assert_eq!(keys, ["audio.volume", "audio.muted", "theme"]);
+}
+
+// Filled in by a web form, so numbers and flags may arrive as strings
+#[derive(Debug)]
+struct IntakeMap(HashMap<String, Value>);
+
+map_to_struct! {
+    #[coerce]
+    IntakeMap => #[derive(Debug, Type)] struct Intake {
+        weight_kg: f32,
+        age: u8,
+        indoor: bool,
+        visits: Option<i32>,
+        name: String,
+    }
+}
+
+#[derive(Debug, MapToStruct)]
+struct Scale {
+    #[map_to_struct(coerce)]
+    grams: u32,
+    label: String,
+}
+
+#[test]
+fn coerce_converts_loosely_typed_values_and_reports_them() {
+    let intake = |entries: Value| IntakeMap(serde_json::from_value(entries).unwrap());
+    let form = intake(json!({ "weight_kg": "4.5", "age": 3.0, "indoor": 1, "visits": "2", "name": "Miso" }));
+    let typed = form.to_typed().unwrap();
+    assert_eq!((typed.weight_kg, typed.age, typed.indoor, typed.visits), (4.5, 3, true, Some(2)));
+
+    let report = form.to_typed_report();
+    assert_eq!(report.coerced, ["weight_kg", "age", "indoor", "visits"]);
+    assert!(intake(json!({ "weight_kg": 4.5, "age": 3, "indoor": true, "name": "Miso" })).to_typed_report().coerced.is_empty());
+    assert_eq!(form.into_typed().unwrap().age, 3);
+
+    // Only lossless readings count
+    let lossy = intake(json!({ "weight_kg": 4, "age": 3.5, "indoor": "yes", "name": "Miso" }));
+    let broken: Vec<_> = lossy.to_typed_report().errors.iter().map(|e| e.key().to_string()).collect();
+    assert_eq!(broken, ["age", "indoor"]);
+
+    let patch: IntakePatch = serde_json::from_value(json!({ "age": "4", "indoor": "false" })).unwrap();
+    assert_eq!((patch.age, patch.indoor), (Some(4), Some(false)));
+
+    // Per field: `label` is still strict
+    let scale = |entries: Value| StateMap::<Scale>::from(serde_json::from_value::<HashMap<String, Value>>(entries).unwrap());
+    assert_eq!(scale(json!({ "grams": "250", "label": "kibble" })).to_typed().unwrap().grams, 250);
+    assert!(matches!(scale(json!({ "grams": 250, "label": 7 })).to_typed(), Err(MapToStructError::Invalid { .. })));
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use common::*;
+use map_to_struct::{FieldChange, MapStruct};
+use serde_json::{json, Value};
+
+#[test]
+fn diff_lists_changed_tracked_keys_only() {
+    let before = grooming_state();
+    let mut after = grooming_state();
+    after.set_shedding_score(4);
+    after.set("fur_colour", json!("tabby"));
+    after.remove("nail_trimmed");
+
+    let changes = before.diff(&after);
+    let keys: Vec<_> = changes.iter().map(|change| change.key.as_str()).collect();
+    assert_eq!(keys, ["shedding_score", "nail_trimmed"]);
+    assert_eq!((&changes[1].old, &changes[1].new), (&json!(true), &Value::Null));
+
+    let (old, new) = (before.to_typed().unwrap(), after.to_typed().unwrap());
+    let typed: Vec<_> = old.diff(&new).into_iter().map(|change| (change.key, change.new)).collect();
+    assert_eq!(typed, [("shedding_score".to_string(), json!(4)), ("nail_trimmed".to_string(), json!(false))]);
+    assert!(old.diff(&old).is_empty());
+
+    // Nested fields by dotted key, however each side stores them
+    let object = settings(json!({ "audio": { "volume": 7, "muted": false }, "theme": "dark" }));
+    let dotted = settings(json!({ "audio.volume": 2, "audio.muted": false, "theme": "dark" }));
+    assert_eq!(object.diff(&dotted), [FieldChange { key: "audio.volume".to_string(), old: json!(7), new: json!(2) }]);
+}
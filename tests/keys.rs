@@ -0,0 +1,154 @@
+mod common;
+
+use std::collections::{BTreeMap, HashMap};
+
+use common::*;
+use map_to_struct::{extract_field, insert_field, map_to_struct, MapToStructError};
+use serde_json::{json, Value};
+use specta::{datatype::DataType, Generics, Type, TypeCollection};
+
+#[test]
+fn single_keys_can_be_read_and_written_without_a_record() {
+    let mut map = grooming_state().into_map();
+    insert_field(&mut map, "shedding_score", &3u8).unwrap();
+
+    assert_eq!(extract_field::<u8>(&map, "shedding_score").unwrap(), 3);
+    assert_eq!(extract_field::<Option<u8>>(&map, "whisker_count").unwrap(), None);
+    assert!(matches!(extract_field::<u8>(&map, "brush_type"), Err(MapToStructError::Invalid { .. })));
+}
+
+#[test]
+fn single_fields_have_typed_getters_and_setters() {
+    let mut map = grooming_state();
+    map.set_shedding_score(4);
+    assert_eq!(map.shedding_score().unwrap(), 4);
+    assert_eq!(map.get("shedding_score"), Some(&json!(4)));
+
+    // Same defaults and checks as a full conversion
+    map.remove("nail_trimmed");
+    assert!(!map.nail_trimmed().unwrap());
+    map.set("brush_type", json!("comb"));
+    assert!(matches!(map.brush_type(), Err(MapToStructError::Validation { .. })));
+}
+
+#[test]
+fn key_enum_round_trips_through_strings() {
+    let keys: Vec<_> = GroomingRecordKey::ALL.iter().map(|key| key.as_str()).collect();
+    assert_eq!(keys, ["fur_length_cm", "brush_type", "shedding_score", "nail_trimmed", "favorite_spot"]);
+
+    assert_eq!("brush_type".parse(), Ok(GroomingRecordKey::BrushType));
+    assert_eq!("brush".parse::<GroomingRecordKey>().unwrap_err().to_string(), "Unknown key brush");
+    assert_eq!(serde_json::to_value(GroomingRecordKey::FurLengthCm).unwrap(), json!("fur_length_cm"));
+    assert!(serde_json::from_value::<GroomingRecordKey>(json!("fur_length")).is_err());
+
+    // Variants follow the field, the string follows the map key
+    assert_eq!(BrushKey::BrushType.as_str(), "kind");
+    assert_eq!(serde_json::from_value::<BrushKey>(json!("furLengthCm")).unwrap(), BrushKey::FurLengthCm);
+
+    let DataType::Enum(key_type) = GroomingRecordKey::inline(&mut TypeCollection::default(), Generics::NONE) else {
+        panic!("expected an enum type");
+    };
+    let names: Vec<_> = key_type.variants().iter().map(|(name, _)| name.as_ref()).collect();
+    assert_eq!(names, keys);
+}
+
+#[test]
+fn keys_follow_rename_rules_and_aliases() {
+    let legacy = BrushMap(serde_json::from_value(json!({ "furLengthCm": 3, "brush_type": "pin" })).unwrap());
+    let brush = legacy.to_typed().unwrap();
+    assert_eq!((brush.fur_length_cm, brush.brush_type.as_str()), (3, "pin"));
+
+    // Write-back always uses the primary key
+    let written = BrushMap::from(brush);
+    assert_eq!(serde_json::to_value(&written.0).unwrap(), json!({ "furLengthCm": 3, "kind": "pin" }));
+
+    let err = BrushMap(BTreeMap::new()).to_typed().unwrap_err();
+    assert!(matches!(err, MapToStructError::Missing { key } if key == "furLengthCm"));
+}
+
+#[test]
+fn strict_maps_reject_keys_no_field_reads() {
+    let brush = BrushMap(serde_json::from_value(json!({ "furLengthCm": 3, "kind": "pin", "colour": "red", "furLength": 2 })).unwrap());
+    assert_eq!(brush.unknown_keys(), ["colour", "furLength"]);
+    let err = brush.to_typed().unwrap_err();
+    assert!(matches!(&err, MapToStructError::UnknownKeys { keys } if keys == &["colour", "furLength"]));
+    assert_eq!(err.to_string(), "Unknown keys colour, furLength");
+    assert!(!brush.to_typed_report().is_ok());
+
+    // Aliases count as known, and lenient maps only report
+    let legacy = BrushMap(serde_json::from_value(json!({ "furLengthCm": 3, "brush": "pin" })).unwrap());
+    assert!(legacy.to_typed().is_ok());
+    let mut map = grooming_state();
+    map.set("fur_colour", json!("tabby"));
+    assert_eq!(map.unknown_keys(), ["fur_colour"]);
+    assert!(map.to_typed().is_ok());
+}
+
+#[derive(Debug)]
+struct MixerMap(HashMap<String, Value>);
+
+map_to_struct! {
+    #[strict]
+    MixerMap => #[derive(Debug)] struct Mixer {
+        #[nested]
+        audio: Audio,
+    }
+}
+
+#[test]
+fn strict_maps_check_keys_inside_nested_objects() {
+    let mixer = |entries: Value| MixerMap(serde_json::from_value(entries).unwrap());
+    let unknown = |map: &MixerMap| match map.to_typed() {
+        Err(MapToStructError::UnknownKeys { keys }) => keys,
+        other => panic!("expected UnknownKeys, got {:?}", other),
+    };
+
+    let object = mixer(json!({ "audio": { "volume": 3, "muted": false, "bass": 9 } }));
+    assert_eq!(object.unknown_keys(), ["audio.bass"]);
+    assert_eq!(unknown(&object), ["audio.bass"]);
+
+    let dotted = mixer(json!({ "audio.volume": 3, "audio.muted": false, "audio.bass": 9 }));
+    assert_eq!(unknown(&dotted), ["audio.bass"]);
+
+    // The object wins, so dotted keys beside it are never read
+    let both = mixer(json!({ "audio": { "volume": 3, "muted": false }, "audio.volume": 5 }));
+    assert_eq!(unknown(&both), ["audio.volume"]);
+
+    assert!(mixer(json!({ "audio": { "volume": 3, "muted": false } })).to_typed().is_ok());
+    assert_eq!(settings(json!({ "audio": { "volume": 3, "treble": 1 }, "theme": "dark" })).unknown_keys(), ["audio.treble"]);
+}
+
+// Declared elsewhere, so the macro doesn't see its visibility
+mod clinic {
+    use std::collections::HashMap;
+
+    use serde_json::Value;
+
+    #[derive(Debug)]
+    pub struct ClinicMap(pub HashMap<String, Value>);
+
+    #[derive(Debug)]
+    pub struct Clinic {
+        pub name: String,
+        pub visits: u32,
+    }
+
+    map_to_struct::map_to_struct! {
+        ClinicMap => Clinic {
+            name: String,
+            visits: u32,
+        }
+    }
+}
+
+#[test]
+fn generated_types_can_be_named_outside_the_module() {
+    use clinic::{Clinic, ClinicKey, ClinicMap, ClinicPatch, ClinicValue};
+
+    let mut map = ClinicMap(serde_json::from_value(json!({ "name": "Whiskers", "visits": 3 })).unwrap());
+    assert_eq!(ClinicKey::ALL, [ClinicKey::Name, ClinicKey::Visits]);
+    assert!(matches!(<Clinic as map_to_struct::MapStruct>::read_value(&map.0, ClinicKey::Visits), Ok(ClinicValue::Visits(3))));
+
+    let patch = ClinicPatch { visits: Some(4), ..Default::default() };
+    assert_eq!(map.apply_patch(patch).unwrap(), [ClinicKey::Visits]);
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::*;
+use map_to_struct::{FieldChange, ObservableMap, StateMap};
+use serde_json::{json, Value};
+
+#[test]
+fn observers_hear_typed_changes_per_key_or_for_all() {
+    let mut state = ObservableMap::new(grooming_state());
+    let scores = Arc::new(Mutex::new(Vec::new()));
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let heard = Arc::clone(&scores);
+    state.subscribe(GroomingRecordKey::SheddingScore, move |change| {
+        let score = |value: &Option<GroomingRecordValue>| match value {
+            Some(GroomingRecordValue::SheddingScore(score)) => Some(*score),
+            _ => None,
+        };
+        heard.lock().unwrap().push((score(&change.old), score(&change.new)));
+    });
+    let emitted = Arc::clone(&events);
+    let all = state.forward_to(move |change| emitted.lock().unwrap().push(change));
+
+    state.set(GroomingRecordValue::SheddingScore(4));
+    state.set(GroomingRecordValue::SheddingScore(4));
+    state.set_raw("fur_colour", json!("tabby"));
+    state.remove("nail_trimmed");
+    let patch = GroomingRecordPatch { shedding_score: Some(2), brush_type: Some("slicker".to_string()), ..Default::default() };
+    assert_eq!(state.apply_patch(patch).unwrap(), [GroomingRecordKey::SheddingScore]);
+    assert_eq!(state.shedding_score().unwrap(), 2);
+
+    assert_eq!(*scores.lock().unwrap(), [(Some(7), Some(4)), (Some(4), Some(2))]);
+    let change = |key: &str, old: Value, new: Value| FieldChange { key: key.to_string(), old, new };
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            change("shedding_score", json!(7), json!(4)),
+            change("nail_trimmed", json!(true), json!(false)),
+            change("shedding_score", json!(4), json!(2)),
+        ]
+    );
+
+    // A value that doesn't convert is sent as `null`
+    state.set_raw("fur_length_cm", json!("long"));
+    assert_eq!(events.lock().unwrap().last(), Some(&change("fur_length_cm", json!(2), Value::Null)));
+    assert!(state.unsubscribe(all));
+    state.set(GroomingRecordValue::FurLengthCm(3));
+    assert_eq!(events.lock().unwrap().len(), 4);
+
+    // Dotted keys belong to the nested field
+    let entries = serde_json::from_value(json!({ "audio.volume": 7, "audio.muted": false, "theme": "dark" })).unwrap();
+    let mut settings = ObservableMap::new(StateMap::<Settings>::from_map(entries));
+    let keys = Arc::new(Mutex::new(Vec::new()));
+    let heard = Arc::clone(&keys);
+    settings.forward_to(move |change| heard.lock().unwrap().push((change.key, change.new)));
+    settings.set_raw("audio.volume", json!(2));
+    assert_eq!(*keys.lock().unwrap(), [("audio".to_string(), json!({ "volume": 2, "muted": false }))]);
+}
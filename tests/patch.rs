@@ -0,0 +1,68 @@
+mod common;
+
+use std::collections::{BTreeMap, HashMap};
+
+use common::*;
+use map_to_struct::{insert_field, map_to_struct, MapStruct, MapToStructError, Patch};
+use serde_json::{json, Value};
+
+#[test]
+fn patches_write_only_present_fields_and_report_changes() {
+    let mut map = grooming_state();
+    let patch: GroomingRecordPatch =
+        serde_json::from_value(json!({ "shedding_score": 3, "brush_type": "slicker", "nail_trimmed": null })).unwrap();
+    assert_eq!(patch.nail_trimmed, None);
+
+    let changed = map.apply_patch(patch).unwrap();
+    assert_eq!(changed, [GroomingRecordKey::SheddingScore]);
+    assert_eq!(map.get("shedding_score"), Some(&json!(3)));
+
+    // A rejected field means nothing is written
+    let patch = GroomingRecordPatch { fur_length_cm: Some(5), shedding_score: Some(11), ..Default::default() };
+    assert_eq!(GroomingRecord::patch_keys(&patch), [GroomingRecordKey::FurLengthCm, GroomingRecordKey::SheddingScore]);
+    assert!(matches!(map.apply_patch(patch), Err(MapToStructError::Validation { key, .. }) if key == "shedding_score"));
+    assert_eq!(map.get("fur_length_cm"), Some(&json!(2)));
+
+    let patch = GroomingRecordPatch { fur_length_cm: Some(5), ..Default::default() };
+    assert_eq!(serde_json::to_value(&patch).unwrap(), json!({ "fur_length_cm": 5 }));
+    assert!(serde_json::from_value::<GroomingRecordPatch>(json!({ "shedding_score": "lots" })).is_err());
+}
+
+#[test]
+fn null_clears_fields_that_take_it() {
+    let mut notes = NotesMap(serde_json::from_value(json!({ "text": "hi", "edit": "brushed" })).unwrap());
+    let patch: NotesPatch = serde_json::from_value(json!({ "text": null, "edit": null })).unwrap();
+    assert_eq!((&patch.text, &patch.edit), (&Some(None), &Some(Patch::Null)));
+    assert_eq!(serde_json::to_value(&patch).unwrap(), json!({ "text": null, "edit": null }));
+
+    assert_eq!(notes.apply_patch(patch).unwrap(), [NotesKey::Text, NotesKey::Edit]);
+    let cleared = notes.to_typed().unwrap();
+    assert_eq!((cleared.text, cleared.edit), (None, Patch::Null));
+
+    // Absent keys still leave the field alone
+    let patch: NotesPatch = serde_json::from_value(json!({ "text": "matted" })).unwrap();
+    assert_eq!(notes.apply_patch(patch).unwrap(), [NotesKey::Text]);
+    assert_eq!(notes.0.get("edit"), Some(&Value::Null));
+}
+
+#[derive(Debug)]
+struct CoatMap(HashMap<String, Value>);
+
+map_to_struct! {
+    CoatMap => #[derive(Debug)] struct CoatPattern {
+        // JSON keys are strings, so any entry fails to serialize
+        spots: BTreeMap<Vec<u8>, u8>,
+    }
+}
+
+#[test]
+fn unserializable_values_are_errors_not_panics() {
+    let spots = BTreeMap::from([(vec![1], 2)]);
+    let mut map = CoatMap(HashMap::new());
+    let err = map.apply_patch(CoatPatternPatch { spots: Some(spots.clone()) }).unwrap_err();
+    assert!(matches!(&err, MapToStructError::Unserializable { key, .. } if key == "spots"));
+    assert!(err.to_string().starts_with("Cannot serialize spots: "));
+    assert!(map.0.is_empty());
+
+    assert!(matches!(insert_field(&mut map.0, "spots", &spots), Err(MapToStructError::Unserializable { .. })));
+}
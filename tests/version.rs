@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use map_to_struct::{map_to_struct, MapToStruct, MapToStructError, StateMap, ValueMap, VERSION_KEY};
+use serde_json::{json, Value};
+use specta::Type;
+
+// Version 1 renamed `clinic` to `name`, version 2 switched to centimetres
+#[derive(Debug, Type, MapToStruct)]
+#[map_to_struct(version = 2, migrate = migrate_visit, strict)]
+struct Visit {
+    name: String,
+    fur_length_cm: i32,
+}
+
+fn migrate_visit(from: u32, map: &mut dyn ValueMap) {
+    match from {
+        0 => {
+            if let Some(name) = map.remove("clinic") {
+                map.insert("name".to_string(), name);
+            }
+        }
+        1 => {
+            if let Some(inches) = map.remove("fur_length_in").and_then(|v| v.as_f64()) {
+                map.insert("fur_length_cm".to_string(), json!((inches * 2.54).round() as i32));
+            }
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn old_maps_are_migrated_before_converting() {
+    let state = |entries: Value| StateMap::<Visit>::from(serde_json::from_value::<HashMap<String, Value>>(entries).unwrap());
+
+    let mut unversioned = state(json!({ "clinic": "Whiskers", "fur_length_in": 2 }));
+    let visit = unversioned.to_typed().unwrap();
+    assert_eq!((visit.name.as_str(), visit.fur_length_cm), ("Whiskers", 5));
+    assert!(unversioned.get("clinic").is_some(), "to_typed migrates a copy");
+
+    let written = StateMap::from(visit);
+    assert_eq!(written.get(VERSION_KEY), Some(&json!(2)));
+    assert!(unversioned.upgrade().unwrap());
+    assert_eq!(unversioned, written);
+    assert!(!unversioned.upgrade().unwrap());
+
+    // Only the steps after the stored version run
+    let v1 = state(json!({ "__version": 1, "name": "Purrfect", "clinic": "stale", "fur_length_in": 1 }));
+    assert!(matches!(v1.to_typed(), Err(MapToStructError::UnknownKeys { keys }) if keys == ["clinic"]));
+    assert_eq!(state(json!({ "__version": 1, "name": "Purrfect", "fur_length_in": 1 })).into_typed().unwrap().fur_length_cm, 3);
+
+    let newer = state(json!({ "__version": 3, "name": "Whiskers", "fur_length_cm": 5 }));
+    let err = newer.to_typed().unwrap_err();
+    assert!(matches!(err, MapToStructError::UnsupportedVersion { version: 3, supported: 2 }));
+    assert_eq!(newer.to_typed_report().errors[0].key(), VERSION_KEY);
+}
+
+#[derive(Debug, Clone, PartialEq, Type, MapToStruct)]
+#[map_to_struct(version = 1)]
+struct Volume {
+    level: u8,
+}
+
+#[derive(Debug)]
+struct DeskMap(HashMap<String, Value>);
+
+map_to_struct! {
+    DeskMap => #[derive(Debug)] struct Desk {
+        gain: u8,
+        #[nested]
+        volume: Volume,
+    }
+}
+
+#[test]
+fn nested_versioned_structs_write_no_version() {
+    let desk = Desk { gain: 1, volume: Volume { level: 3 } };
+    let mut map = DeskMap::from(desk);
+    assert_eq!(serde_json::to_value(&map.0).unwrap(), json!({ "gain": 1, "volume.level": 3 }));
+
+    let patch = DeskPatch { volume: Some(Volume { level: 4 }), ..Default::default() };
+    assert_eq!(serde_json::to_value(&patch).unwrap(), json!({ "volume": { "level": 4 } }));
+    map.apply_patch(patch).unwrap();
+    assert_eq!(serde_json::to_value(&map.0).unwrap(), json!({ "gain": 1, "volume.level": 4 }));
+    assert_eq!(serde_json::to_value(DeskValue::Volume(Volume { level: 4 })).unwrap(), json!({ "level": 4 }));
+
+    // On its own it's the outermost struct again
+    assert_eq!(StateMap::from(Volume { level: 4 }).get(VERSION_KEY), Some(&json!(1)));
+}